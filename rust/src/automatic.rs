@@ -0,0 +1,184 @@
+// The game moves some cards to the foundation by itself, without the player
+// asking for it. Solutions only list the moves the player has to make, and the
+// states in them are what the player sees on screen after the game is done
+// moving cards.
+//
+// The game collects a card when it is on top of a column or in a cell and:
+// - it is the flower card, or
+// - it is a numbered card that can go to the foundation, and every card that
+//   could still be placed on top of it is already in the foundation. That is
+//   every numbered foundation is at least one below the card, so the 1s are
+//   always collected, the 2s once all three 1s are collected, and so on.
+//
+// Dragons are never collected automatically, the player has to press the
+// button. Cards are moved one at a time, and moving one card can uncover
+// another that is then moved as well.
+
+use crate::card::*;
+use crate::game::GameMove;
+use crate::game_state::*;
+use crate::undo::Undo;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AutomaticMoves {
+    // Behave like the game, see apply_automatic_moves
+    #[default]
+    Emulated,
+    // The player makes every move, including all moves to the foundation
+    Manual,
+}
+
+impl GameState {
+    fn is_collected_automatically(&self, card: &Card) -> bool {
+        match (card.suit, card.value) {
+            (Suit::Special, _) => true,
+            (Suit::FaceDown, _) | (_, None) => false,
+            (suit, Some(value)) => {
+                self.top_right_storage[suit as usize] == value - 1
+                    && [Suit::Red, Suit::Green, Suit::Black]
+                        .iter()
+                        .all(|other| self.top_right_storage[*other as usize] + 1 >= value)
+            }
+        }
+    }
+
+    // The next move the game would make by itself, if any. Columns are checked
+    // left to right, then the cells.
+    pub fn automatic_move(&self) -> Option<GameMove> {
+        for column in 0..8 {
+            if self.columns[column]
+                .last()
+                .is_some_and(|card| self.is_collected_automatically(card))
+            {
+                return Some(GameMove::ColumnToTopRightStorage { column });
+            }
+        }
+
+        (0..self.top_left_storage.len())
+            .find(|i| self.is_collected_automatically(&self.top_left_storage[*i]))
+            .map(|top_left_index| GameMove::TopLeftToTopRightStorage { top_left_index })
+    }
+
+    // Makes every move the game would make by itself, in the order the game
+    // makes them. Undoing the returned records in reverse order restores the
+    // previous state.
+    pub fn apply_automatic_moves(&mut self) -> Vec<Undo> {
+        let mut undo = vec![];
+        while let Some(game_move) = self.automatic_move() {
+            undo.push(self.apply_unchecked(&game_move));
+        }
+        undo
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::deal::random_position;
+    use googletest::assert_that;
+    use googletest::matchers::*;
+
+    fn moves(state: &mut GameState) -> Vec<GameMove> {
+        state
+            .apply_automatic_moves()
+            .iter()
+            .map(|undo| undo.game_move())
+            .collect()
+    }
+
+    #[test]
+    fn test_flower_and_ones() {
+        let mut state: GameState = GameState::parse_unchecked(
+            "1: R2 F\n\
+             2: G1\n\
+             3: B3 B1\n\
+             4: R1 R2\n\
+             5:\n\
+             6:\n\
+             7:\n\
+             8:\n",
+        )
+        .unwrap();
+
+        assert_that!(
+            moves(&mut state),
+            eq(vec![
+                GameMove::ColumnToTopRightStorage { column: 0 },
+                GameMove::ColumnToTopRightStorage { column: 1 },
+                GameMove::ColumnToTopRightStorage { column: 2 },
+            ])
+        );
+        // R2 can go to the foundation, but R1 is still buried
+        assert_that!(state.top_right_storage, eq([1, 0, 1, 1]));
+    }
+
+    #[test]
+    fn test_waits_for_lower_cards() {
+        let mut state: GameState = GameState::parse_unchecked(
+            "cells: G3 B3\n\
+             foundation: F1 R2 G2 B1\n\
+             1: R3\n\
+             2: B2\n\
+             3:\n\
+             4:\n\
+             5:\n\
+             6:\n\
+             7:\n\
+             8:\n",
+        )
+        .unwrap();
+
+        // The black 2 could still hold the red 1 or the green 1, but those are
+        // already collected. R3 and G3 have to wait for the black 2.
+        assert_that!(
+            moves(&mut state),
+            eq(vec![
+                GameMove::ColumnToTopRightStorage { column: 1 },
+                GameMove::ColumnToTopRightStorage { column: 0 },
+                GameMove::TopLeftToTopRightStorage { top_left_index: 0 },
+                GameMove::TopLeftToTopRightStorage { top_left_index: 0 },
+            ])
+        );
+        assert_that!(state.top_right_storage, eq([1, 3, 3, 3]));
+    }
+
+    #[test]
+    fn test_keeps_useful_cards() {
+        let mut state: GameState = GameState::parse_unchecked(
+            "foundation: F1 R1 G0 B0\n\
+             1: R2\n\
+             2: G2 G1\n\
+             3: B1 B2\n\
+             4: RD\n\
+             5:\n\
+             6:\n\
+             7:\n\
+             8:\n",
+        )
+        .unwrap();
+
+        // G1 goes, but R2 stays as the black 1 could still be placed on it
+        assert_that!(
+            moves(&mut state),
+            eq(vec![GameMove::ColumnToTopRightStorage { column: 1 }])
+        );
+        assert_that!(state.automatic_move(), none());
+    }
+
+    #[test]
+    fn test_undo() {
+        for seed in 0..30 {
+            let before = random_position(seed, 40);
+            let mut state = before.clone();
+            let undo = state.apply_automatic_moves();
+            assert_that!(state.automatic_move(), none());
+
+            for record in undo.iter().rev() {
+                state.undo(record);
+            }
+            assert_that!(&state.columns, eq(&before.columns));
+            assert_that!(&state.top_left_storage, eq(&before.top_left_storage));
+            assert_that!(state.top_right_storage, eq(before.top_right_storage));
+        }
+    }
+}
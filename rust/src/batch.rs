@@ -0,0 +1,201 @@
+// Solving many deals at once, on several threads. Each deal is solved by its
+// own Game, so the results are the same as solving the deals one at a time.
+
+use crate::game::{Game, SolveResult};
+use crate::game_state::GameState;
+use crate::stats::SearchStats;
+use std::collections::HashMap;
+use std::panic;
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+
+#[derive(Debug, Clone)]
+pub struct BatchResult {
+    // The position of the deal in the input
+    pub index: usize,
+    pub result: SolveResult,
+    pub stats: SearchStats,
+}
+
+pub struct Batch {
+    threads: usize,
+    new_game: Arc<dyn Fn() -> Game + Send + Sync>,
+}
+
+impl Batch {
+    // new_game is called on a worker thread just before each deal is solved,
+    // so limits like a deadline apply to each deal separately. Uses one thread
+    // per core by default.
+    pub fn new(new_game: impl Fn() -> Game + Send + Sync + 'static) -> Batch {
+        Batch {
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            new_game: Arc::new(new_game),
+        }
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Batch {
+        assert!(threads > 0, "A batch needs at least one thread");
+        self.threads = threads;
+        self
+    }
+
+    // Starts solving the deals, and returns their results in the same order.
+    // The deals are taken from the iterator as threads become free, so it can
+    // generate them lazily. Dropping the results stops the threads once they
+    // finish their current deal.
+    pub fn solve<I>(&self, states: I) -> BatchResults
+    where
+        I: IntoIterator<Item = GameState>,
+        I::IntoIter: Send + 'static,
+    {
+        let states = Arc::new(Mutex::new(states.into_iter().enumerate()));
+        let (sender, receiver) = channel();
+
+        let workers = (0..self.threads)
+            .map(|_| {
+                let states = Arc::clone(&states);
+                let new_game = Arc::clone(&self.new_game);
+                let sender = sender.clone();
+                thread::spawn(move || loop {
+                    let next = states.lock().unwrap().next();
+                    let Some((index, state)) = next else {
+                        break;
+                    };
+
+                    let (result, stats) = new_game().solve_with_stats(state);
+                    let result = BatchResult {
+                        index,
+                        result,
+                        stats,
+                    };
+                    if sender.send(result).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+
+        BatchResults {
+            receiver,
+            pending: HashMap::new(),
+            next: 0,
+            workers,
+        }
+    }
+}
+
+pub struct BatchResults {
+    receiver: Receiver<BatchResult>,
+    // Results that finished before the ones ahead of them in the input
+    pending: HashMap<usize, BatchResult>,
+    next: usize,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Iterator for BatchResults {
+    type Item = BatchResult;
+
+    // Waits for the next deal to be solved. A panic on a worker thread is
+    // passed on to the caller.
+    fn next(&mut self) -> Option<BatchResult> {
+        loop {
+            if let Some(result) = self.pending.remove(&self.next) {
+                self.next += 1;
+                return Some(result);
+            }
+
+            match self.receiver.recv() {
+                Ok(result) => {
+                    self.pending.insert(result.index, result);
+                }
+                // Every worker has stopped
+                Err(_) => {
+                    for worker in self.workers.drain(..) {
+                        if let Err(payload) = worker.join() {
+                            panic::resume_unwind(payload);
+                        }
+                    }
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::deal::deal;
+    use crate::limits::{Limit, Limits};
+    use googletest::matchers::*;
+    use googletest::{assert_that, matches_pattern};
+
+    fn states() -> Vec<GameState> {
+        vec![
+            include_str!("../deals/deal1.txt").parse().unwrap(),
+            // No solution
+            deal(9),
+            include_str!("../deals/deal0.txt").parse().unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_same_as_one_at_a_time() {
+        let expected: Vec<(Option<usize>, SearchStats)> = states()
+            .into_iter()
+            .map(|state| {
+                let (result, stats) = Game::new().solve_with_stats(state);
+                (
+                    result.solution().map(|solution| solution.move_count()),
+                    stats,
+                )
+            })
+            .collect();
+
+        for threads in [1, 2, 5] {
+            let results: Vec<BatchResult> = Batch::new(Game::new)
+                .with_threads(threads)
+                .solve(states())
+                .collect();
+
+            assert_that!(results.len(), eq(expected.len()));
+            for (i, (result, (moves, stats))) in results.into_iter().zip(&expected).enumerate() {
+                assert_that!(result.index, eq(i));
+                assert_that!(
+                    result
+                        .result
+                        .solution()
+                        .map(|solution| solution.move_count()),
+                    eq(*moves)
+                );
+                assert_that!(result.stats.expanded_nodes, eq(stats.expanded_nodes));
+            }
+        }
+    }
+
+    #[test]
+    fn test_outcomes() {
+        let batch = Batch::new(|| {
+            Game::new().with_limits(Limits {
+                max_expanded_nodes: Some(10),
+                ..Limits::default()
+            })
+        });
+
+        let results: Vec<BatchResult> = batch.solve(states()).collect();
+        assert_that!(results.len(), eq(3));
+        for result in &results {
+            assert_that!(
+                result.result,
+                matches_pattern!(SolveResult::LimitReached {
+                    limit: eq(Limit::ExpandedNodes),
+                })
+            );
+            assert_that!(result.stats.expanded_nodes, eq(10));
+        }
+
+        assert_that!(batch.solve(vec![]).next(), none());
+    }
+}
@@ -1,7 +1,5 @@
 use crate::card::Suit::Special;
 
-use std::fmt;
-use std::fmt::Formatter;
 use std::hash::Hash;
 
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
@@ -36,7 +34,7 @@ pub struct Card {
 
 impl Card {
     pub fn is_dragon(&self) -> bool {
-        self.value == None
+        self.value.is_none()
     }
 
     pub fn is_dragon_with_suit(&self, suit: Suit) -> bool {
@@ -62,24 +60,7 @@ impl Card {
 
         assert_ne!(other.value, None);
 
-        return self.suit != other.suit && self.value.unwrap() == other.value.unwrap() - 1;
-    }
-}
-
-impl fmt::Display for Card {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let colors = ["🟦", "🟥", "🟩", "⬛"];
-        f.write_str(&match { self.suit } {
-            Suit::FaceDown => String::from("xxx"),
-            _ => format!(
-                "{}{}",
-                colors[self.suit as usize],
-                match { self.value } {
-                    None => String::from("x"),
-                    Some(value) => format!("{}", value),
-                }
-            ),
-        })
+        self.suit != other.suit && self.value.unwrap() == other.value.unwrap() - 1
     }
 }
 
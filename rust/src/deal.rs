@@ -0,0 +1,204 @@
+// Seeded generation of deals and partially played positions.
+//
+// The random number generator is implemented here rather than taken from a
+// crate, so that a seed always produces the same deal regardless of
+// dependency upgrades. Deals are used as test and benchmark fixtures, so they
+// need to stay reproducible forever.
+
+use crate::card::*;
+use crate::game_state::*;
+
+// SplitMix64, see https://prng.di.unimi.it/splitmix64.c
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniformly distributed in 0..bound
+    pub fn below(&mut self, bound: usize) -> usize {
+        assert!(bound > 0);
+        let bound = bound as u64;
+
+        // Reject the values that would make the lowest results slightly more
+        // likely than the others
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return (value % bound) as usize;
+            }
+        }
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        // Fisher-Yates
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+// All 40 cards: 1 to 9 and four dragons in each of the three suits, plus the
+// flower card
+pub fn deck() -> Vec<Card> {
+    let mut cards = vec![Card {
+        suit: Suit::Special,
+        value: Some(1),
+    }];
+
+    for suit in [Suit::Red, Suit::Green, Suit::Black] {
+        for value in 1..=9 {
+            cards.push(Card {
+                suit,
+                value: Some(value),
+            });
+        }
+
+        for _ in 0..4 {
+            cards.push(Card { suit, value: None });
+        }
+    }
+
+    cards
+}
+
+// Shuffles the deck and deals it like the game does, one card to each column
+// in turn, giving five cards per column.
+pub fn deal(seed: u64) -> GameState {
+    let mut cards = deck();
+    Rng::new(seed).shuffle(&mut cards);
+
+    let mut state = GameState {
+        top_left_storage: vec![],
+        top_right_storage: [0; 4],
+        columns: Default::default(),
+    };
+
+    for (i, card) in cards.into_iter().enumerate() {
+        state.columns[i % 8].push(card);
+    }
+
+    state
+}
+
+// A position reached by playing up to move_count random legal moves from the
+// deal for the seed. Fewer moves are played if the game runs out of legal
+// moves first.
+pub fn random_position(seed: u64, move_count: usize) -> GameState {
+    let mut state = deal(seed);
+    // Use a separate stream for the moves, so the deal is the same as the one
+    // returned by deal(seed)
+    let mut rng = Rng::new(!seed);
+
+    for _ in 0..move_count {
+        let moves = state.legal_moves();
+        if moves.is_empty() {
+            break;
+        }
+
+        let game_move = &moves[rng.below(moves.len())];
+        state
+            .apply(game_move)
+            .expect("legal_moves only returns legal moves");
+    }
+
+    state
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use googletest::assert_that;
+    use googletest::matchers::*;
+
+    fn cards_in_play(state: &GameState) -> usize {
+        state
+            .columns
+            .iter()
+            .map(|column| column.len())
+            .sum::<usize>()
+            + state
+                .top_left_storage
+                .iter()
+                .filter(|card| card.suit != Suit::FaceDown)
+                .count()
+    }
+
+    #[test]
+    fn test_rng_is_stable() {
+        // Changing these values changes every generated deal
+        let mut rng = Rng::new(1234567);
+        assert_that!(rng.next_u64(), eq(6457827717110365317));
+        assert_that!(rng.next_u64(), eq(3203168211198807973));
+    }
+
+    #[test]
+    fn test_deck() {
+        let mut deck = deck();
+        assert_that!(deck.len(), eq(40));
+
+        deck.sort();
+        deck.dedup();
+        // 27 numbered cards, one dragon per suit and the flower
+        assert_that!(deck.len(), eq(31));
+    }
+
+    #[test]
+    fn test_deal() {
+        let state = deal(42);
+
+        for column in &state.columns {
+            assert_that!(column.len(), eq(5));
+        }
+        assert_that!(state.top_left_storage.is_empty(), eq(true));
+        assert_that!(state.top_right_storage, eq([0; 4]));
+
+        let mut cards: Vec<Card> = state.columns.concat();
+        cards.sort();
+        let mut expected = deck();
+        expected.sort();
+        assert_that!(cards, eq(expected));
+    }
+
+    #[test]
+    fn test_deal_is_reproducible() {
+        assert_that!(deal(7).columns, eq(deal(7).columns));
+        assert_that!(deal(7).columns, not(eq(deal(8).columns)));
+    }
+
+    #[test]
+    fn test_random_position() {
+        assert_that!(random_position(3, 0).columns, eq(deal(3).columns));
+
+        let state = random_position(3, 20);
+        assert_that!(state.columns, not(eq(deal(3).columns)));
+        assert_that!(state.columns, eq(random_position(3, 20).columns));
+
+        let collected = state
+            .top_right_storage
+            .iter()
+            .map(|v| *v as usize)
+            .sum::<usize>();
+        let collected_dragons = 4 * state
+            .top_left_storage
+            .iter()
+            .filter(|card| card.suit == Suit::FaceDown)
+            .count();
+        assert_that!(
+            cards_in_play(&state) + collected + collected_dragons,
+            eq(40)
+        );
+    }
+}
@@ -0,0 +1,423 @@
+// Pictures of positions and solutions, for bug reports and for sharing. A
+// position is drawn as an SVG image laid out like the game table, and a
+// solution as a single HTML page that steps through the replay, with the SVG
+// for every step inline so the page works offline.
+//
+// The table has the three cells, the dragon buttons, the flower slot and the
+// foundation along the top, and the eight columns below. A dragon button is
+// lit when the dragons of its suit can be collected.
+
+use crate::card::*;
+use crate::game_state::GameState;
+use crate::replay::{Place, Replay};
+use std::fmt;
+use std::fmt::{Formatter, Write};
+
+const CARD_WIDTH: usize = 90;
+const CARD_HEIGHT: usize = 120;
+// From the left of one column to the left of the next
+const PITCH: usize = 104;
+// How much of each card shows above the next one in a column
+const OVERLAP: usize = 26;
+const MARGIN: usize = 20;
+const COLUMNS_TOP: usize = MARGIN + CARD_HEIGHT + 30;
+
+const TABLE_COLOUR: &str = "#2f5d50";
+const SOURCE_COLOUR: &str = "#e67e22";
+const DESTINATION_COLOUR: &str = "#3498db";
+const AUTOMATIC_COLOUR: &str = "#f1c40f";
+
+fn suit_colour(suit: Suit) -> &'static str {
+    match suit {
+        Suit::Special => "#8e44ad",
+        Suit::Red => "#c0392b",
+        Suit::Green => "#1e8449",
+        Suit::Black => "#222222",
+        Suit::FaceDown => "#34495e",
+    }
+}
+
+fn left(slot: usize) -> usize {
+    MARGIN + slot * PITCH
+}
+
+fn card_top(index: usize) -> usize {
+    COLUMNS_TOP + index * OVERLAP
+}
+
+fn width() -> usize {
+    left(7) + CARD_WIDTH + MARGIN
+}
+
+// Tall enough for columns of the given number of cards
+fn height(rows: usize) -> usize {
+    card_top(rows.max(1) - 1) + CARD_HEIGHT + MARGIN
+}
+
+// Where a place is drawn, as the left and top of its first card. Cells are
+// above the first three columns, and the foundation above the last four, the
+// flower first.
+fn position(state: &GameState, place: Place) -> (usize, usize) {
+    match place {
+        Place::Cell(cell) => (left(cell), MARGIN),
+        Place::Foundation(suit) => (left(4 + suit as usize - Suit::Special as usize), MARGIN),
+        Place::Column(column) => (left(column), card_top(state.columns[column].len())),
+    }
+}
+
+fn write_card(out: &mut String, card: &Card, x: usize, y: usize) -> fmt::Result {
+    let colour = suit_colour(card.suit);
+    if card.suit == Suit::FaceDown {
+        return writeln!(
+            out,
+            r##"<rect x="{x}" y="{y}" width="{CARD_WIDTH}" height="{CARD_HEIGHT}" rx="6" fill="{colour}" stroke="#111"/>"##
+        );
+    }
+
+    let label = match (card.suit, card.value) {
+        (Suit::Special, _) => String::from("F"),
+        (_, None) => String::from("D"),
+        (_, Some(value)) => value.to_string(),
+    };
+    writeln!(
+        out,
+        r##"<rect x="{x}" y="{y}" width="{CARD_WIDTH}" height="{CARD_HEIGHT}" rx="6" fill="#fdfbf5" stroke="#111"/>"##
+    )?;
+    writeln!(
+        out,
+        r#"<text x="{}" y="{}" font-size="18" font-weight="bold" fill="{colour}">{label}</text>"#,
+        x + 8,
+        y + 20
+    )?;
+    writeln!(
+        out,
+        r#"<text x="{}" y="{}" font-size="40" text-anchor="middle" fill="{colour}">{label}</text>"#,
+        x + CARD_WIDTH / 2,
+        y + CARD_HEIGHT / 2 + 14
+    )
+}
+
+fn write_empty(out: &mut String, x: usize, y: usize) -> fmt::Result {
+    writeln!(
+        out,
+        r##"<rect x="{x}" y="{y}" width="{CARD_WIDTH}" height="{CARD_HEIGHT}" rx="6" fill="none" stroke="#9bb8ad" stroke-dasharray="6 4"/>"##
+    )
+}
+
+// An outline around a place. For a column, count is the number of cards to
+// go around at its top, 0 outlines the slot just below the top card, where
+// the next card would go.
+fn write_mark(
+    out: &mut String,
+    state: &GameState,
+    place: Place,
+    count: usize,
+    colour: &str,
+) -> fmt::Result {
+    let (x, mut y) = position(state, place);
+    let mut height = CARD_HEIGHT;
+    if let (Place::Column(_), 1..) = (place, count) {
+        y -= count * OVERLAP;
+        height += (count - 1) * OVERLAP;
+    }
+    writeln!(
+        out,
+        r#"<rect x="{}" y="{}" width="{}" height="{}" rx="8" fill="none" stroke="{colour}" stroke-width="4"/>"#,
+        x - 4,
+        y - 4,
+        CARD_WIDTH + 8,
+        height + 8
+    )
+}
+
+// The table for a position, with room for columns of rows cards, and outlines
+// for the given places
+fn write_table(
+    out: &mut String,
+    state: &GameState,
+    rows: usize,
+    marks: &[(Place, usize, &str)],
+) -> fmt::Result {
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}" font-family="sans-serif">"#,
+        width(),
+        height(rows)
+    )?;
+    writeln!(
+        out,
+        r#"<rect width="100%" height="100%" fill="{TABLE_COLOUR}"/>"#
+    )?;
+
+    for cell in 0..3 {
+        match state.top_left_storage.get(cell) {
+            None => write_empty(out, left(cell), MARGIN)?,
+            Some(card) => write_card(out, card, left(cell), MARGIN)?,
+        }
+    }
+
+    for (i, suit) in [Suit::Red, Suit::Green, Suit::Black]
+        .into_iter()
+        .enumerate()
+    {
+        let fill = match state.can_collect_dragons(suit) {
+            true => suit_colour(suit),
+            false => "#4a6b60",
+        };
+        writeln!(
+            out,
+            r##"<circle cx="{}" cy="{}" r="14" fill="{fill}" stroke="#111"/>"##,
+            left(3) + CARD_WIDTH / 2,
+            MARGIN + 20 + i * 40
+        )?;
+    }
+
+    for suit in [Suit::Special, Suit::Red, Suit::Green, Suit::Black] {
+        let (x, y) = position(state, Place::Foundation(suit));
+        match state.top_right_storage[suit as usize] {
+            0 => write_empty(out, x, y)?,
+            value => write_card(
+                out,
+                &Card {
+                    suit,
+                    value: Some(value),
+                },
+                x,
+                y,
+            )?,
+        }
+    }
+
+    for (i, column) in state.columns.iter().enumerate() {
+        if column.is_empty() {
+            write_empty(out, left(i), card_top(0))?;
+        }
+        for (index, card) in column.iter().enumerate() {
+            write_card(out, card, left(i), card_top(index))?;
+        }
+    }
+
+    for &(place, count, colour) in marks {
+        write_mark(out, state, place, count, colour)?;
+    }
+
+    writeln!(out, "</svg>")
+}
+
+pub struct Svg<'a>(pub &'a GameState);
+
+impl GameState {
+    pub fn svg(&self) -> Svg<'_> {
+        Svg(self)
+    }
+}
+
+impl fmt::Display for Svg<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let rows = self.0.columns.iter().map(Vec::len).max().unwrap_or(0);
+        let mut out = String::new();
+        write_table(&mut out, self.0, rows, &[])?;
+        f.write_str(&out)
+    }
+}
+
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Solution</title>
+<style>
+body { font-family: sans-serif; margin: 20px; }
+figure { margin: 0; }
+figcaption { height: 3em; }
+.controls { margin-bottom: 10px; }
+.key span { display: inline-block; width: 12px; height: 12px; margin: 0 4px 0 12px; }
+</style>
+</head>
+<body>
+"#;
+
+const HTML_SCRIPT: &str = r#"<script>
+const steps = document.querySelectorAll(".step");
+const slider = document.getElementById("slider");
+const playButton = document.getElementById("play");
+let current = 0;
+let timer = null;
+
+function show(step) {
+  current = Math.max(0, Math.min(steps.length - 1, step));
+  steps.forEach((figure, i) => figure.hidden = i !== current);
+  slider.value = current;
+}
+
+function pause() {
+  clearInterval(timer);
+  timer = null;
+  playButton.textContent = "play";
+}
+
+function play() {
+  if (timer !== null) {
+    pause();
+    return;
+  }
+  if (current === steps.length - 1) {
+    show(0);
+  }
+  playButton.textContent = "pause";
+  timer = setInterval(() => {
+    show(current + 1);
+    if (current === steps.length - 1) {
+      pause();
+    }
+  }, 800);
+}
+
+document.getElementById("first").onclick = () => show(0);
+document.getElementById("back").onclick = () => show(current - 1);
+document.getElementById("next").onclick = () => show(current + 1);
+document.getElementById("last").onclick = () => show(steps.length - 1);
+playButton.onclick = play;
+slider.oninput = () => show(Number(slider.value));
+document.addEventListener("keydown", event => {
+  switch (event.key) {
+    case "ArrowLeft": show(current - 1); break;
+    case "ArrowRight": show(current + 1); break;
+    case "Home": show(0); break;
+    case "End": show(steps.length - 1); break;
+    case " ": play(); event.preventDefault(); break;
+  }
+});
+</script>
+"#;
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+pub struct Html<'a>(pub &'a Replay);
+
+impl Replay {
+    // A page that steps through the replay, with buttons, a slider and the
+    // arrow keys, or plays it on a timer
+    pub fn html(&self) -> Html<'_> {
+        Html(self)
+    }
+}
+
+impl fmt::Display for Html<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let replay = self.0;
+        let last = replay.steps.len();
+        // Every step uses the same size, so the page doesn't jump around
+        let rows = (0..=last)
+            .flat_map(|step| replay.state(step).columns.iter().map(Vec::len))
+            .max()
+            .unwrap_or(0);
+
+        let mut out = String::from(HTML_HEAD);
+        writeln!(
+            out,
+            r#"<div class="controls">
+<button id="first">first</button>
+<button id="back">back</button>
+<button id="play">play</button>
+<button id="next">next</button>
+<button id="last">last</button>
+<input id="slider" type="range" min="0" max="{last}" value="0">
+</div>
+<div class="key"><span style="background: {SOURCE_COLOUR}"></span>moved from<span style="background: {DESTINATION_COLOUR}"></span>moved to<span style="background: {AUTOMATIC_COLOUR}"></span>moved by the game</div>"#
+        )?;
+
+        for step in 0..=last {
+            let state = replay.state(step);
+            let mut caption = format!("deal, {} moves to go", last);
+            let mut marks = vec![];
+
+            if step > 0 {
+                let replay_step = &replay.steps[step - 1];
+                caption = format!(
+                    "step {} of {}: {}",
+                    step, last, replay_step.movement.game_move
+                );
+                if !replay_step.automatic.is_empty() {
+                    let moves: Vec<String> = replay_step
+                        .automatic
+                        .iter()
+                        .map(|movement| movement.game_move.to_string())
+                        .collect();
+                    caption += &format!("<br>the game then moved: {}", escape(&moves.join(", ")));
+                }
+
+                for movement in &replay_step.automatic {
+                    for &place in &movement.from {
+                        marks.push((place, 0, AUTOMATIC_COLOUR));
+                    }
+                    marks.push((movement.to, 1, AUTOMATIC_COLOUR));
+                }
+                for &place in &replay_step.movement.from {
+                    marks.push((place, 0, SOURCE_COLOUR));
+                }
+                if let Some((place, count)) = replay_step.landed() {
+                    marks.push((place, count, DESTINATION_COLOUR));
+                }
+            }
+            if step == last && state.is_solved() {
+                caption += "<br>solved";
+            }
+
+            let hidden = if step == 0 { "" } else { " hidden" };
+            writeln!(out, r#"<figure class="step"{hidden}>"#)?;
+            writeln!(out, "<figcaption>{}</figcaption>", caption)?;
+            write_table(&mut out, state, rows, &marks)?;
+            writeln!(out, "</figure>")?;
+        }
+
+        out += HTML_SCRIPT;
+        out += "</body>\n</html>\n";
+        f.write_str(&out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::automatic::AutomaticMoves;
+    use crate::game::{Game, GameMove};
+    use googletest::assert_that;
+    use googletest::matchers::*;
+
+    fn start() -> GameState {
+        include_str!("../deals/deal0.txt").parse().unwrap()
+    }
+
+    #[test]
+    fn test_svg() {
+        let svg = start().svg().to_string();
+        assert_that!(svg.starts_with("<svg "), eq(true));
+        assert_that!(svg.trim_end().ends_with("</svg>"), eq(true));
+        // One outline for each card, with a corner and a centre label
+        assert_that!(svg.matches("<text ").count(), eq(40 * 2));
+        // The three cells and the four foundations are empty
+        assert_that!(svg.matches("stroke-dasharray").count(), eq(7));
+        assert_that!(svg.matches("<circle ").count(), eq(3));
+    }
+
+    #[test]
+    fn test_html() {
+        let solution = Game::new().play(start()).unwrap();
+        let moves: Vec<GameMove> = solution.iter().map(|(_, m)| m.clone()).collect();
+        let replay = Replay::new(&start(), &moves, AutomaticMoves::Emulated).unwrap();
+        let html = replay.html().to_string();
+
+        assert_that!(html.matches("<figure").count(), eq(replay.steps.len() + 1));
+        assert_that!(html.matches("<svg ").count(), eq(replay.steps.len() + 1));
+        assert_that!(html.matches(" hidden>").count(), eq(replay.steps.len()));
+        assert_that!(html.contains("solved</figcaption>"), eq(true));
+        // Self contained
+        assert_that!(html.contains("src="), eq(false));
+        assert_that!(html.contains("href="), eq(false));
+    }
+}
@@ -1,10 +1,25 @@
+use crate::automatic::AutomaticMoves;
 use crate::card::*;
 use crate::game_state::*;
+use crate::heuristic::{DefaultHeuristic, Heuristic};
+use crate::limits::{Limit, Limits};
+use crate::progress::{CancellationToken, Progress};
+use crate::state_key::StateKey;
+use crate::stats::SearchStats;
+use crate::validation::Violation;
 use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashSet};
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+use std::fmt::Formatter;
 use std::iter::zip;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum GameMove {
     Start,
     ColumnToTopRightStorage {
@@ -30,130 +45,254 @@ pub enum GameMove {
     },
 }
 
+// Columns and cells are numbered from 1, matching the labels used by the text
+// notation
+impl fmt::Display for GameMove {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            GameMove::Start => f.write_str("start"),
+            GameMove::ColumnToTopRightStorage { column } => {
+                write!(f, "column {} to foundation", column + 1)
+            }
+            GameMove::TopLeftToTopRightStorage { top_left_index } => {
+                write!(f, "cell {} to foundation", top_left_index + 1)
+            }
+            GameMove::CollectDragons { suit } => write!(f, "collect {:?} dragons", suit),
+            GameMove::ColumnToOtherColumn {
+                from_column_index,
+                to_column_index,
+                stack_size,
+            } => write!(
+                f,
+                "{} {} from column {} to column {}",
+                stack_size,
+                if *stack_size == 1 { "card" } else { "cards" },
+                from_column_index + 1,
+                to_column_index + 1
+            ),
+            GameMove::ToTopLeftStorage { column } => write!(f, "column {} to cell", column + 1),
+            GameMove::TopLeftToColumn {
+                top_left_index,
+                column_index,
+            } => write!(
+                f,
+                "cell {} to column {}",
+                top_left_index + 1,
+                column_index + 1
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchMode {
+    // Best-first search on the heuristic, see Game::with_heuristic. Finds a
+    // solution quickly, but it is usually not the shortest one.
+    #[default]
+    Greedy,
+    // A* search on the number of moves made plus a lower bound on the number
+    // of moves left, see lower_bound. The first solution found has the fewest
+    // possible moves.
+    Optimal,
+}
+
+#[derive(Debug, Clone)]
+pub struct Solution {
+    // Starts with the initial position and GameMove::Start, like the result
+    // of Game::play
+    pub steps: Vec<(GameState, GameMove)>,
+    // There is no solution with fewer moves. Only an optimal search that
+    // finished within its limits proves this.
+    pub proven_optimal: bool,
+}
+
+impl Solution {
+    pub fn move_count(&self) -> usize {
+        self.steps.len() - 1
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SolveResult {
+    Solved(Solution),
+    // Every reachable position was searched
+    ProvenUnsolvable,
+    LimitReached {
+        limit: Limit,
+        // The shortest solution seen before the search stopped, which is not
+        // proven to be optimal
+        best: Option<Solution>,
+    },
+    // The start position could not come up in the game, so it wasn't
+    // searched, see GameState::validate
+    InvalidStart(Vec<Violation>),
+}
+
+impl SolveResult {
+    // The solution found, if any, even if the search didn't finish
+    pub fn solution(self) -> Option<Solution> {
+        match self {
+            SolveResult::Solved(solution) => Some(solution),
+            SolveResult::ProvenUnsolvable | SolveResult::InvalidStart(_) => None,
+            SolveResult::LimitReached { best, .. } => best,
+        }
+    }
+}
+
 struct PrioritisedGameState {
     priority: i32,
+    // Decides between states with the same priority
+    tie_break: i32,
     state: GameState,
-    path: Vec<GameState>,
-    moves: Vec<GameMove>,
+    key: StateKey,
+    // The number of moves made to reach this state
+    depth: usize,
+    // Index into Game::nodes
+    node: usize,
 }
 
 impl Eq for PrioritisedGameState {}
 impl Ord for PrioritisedGameState {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        self.priority
+            .cmp(&other.priority)
+            .then(self.tie_break.cmp(&other.tie_break))
     }
 }
 
 impl PartialEq for PrioritisedGameState {
     fn eq(&self, other: &Self) -> bool {
-        self.priority.eq(&other.priority)
+        self.cmp(other) == Ordering::Equal
     }
 }
 
 impl PartialOrd<Self> for PrioritisedGameState {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.priority.partial_cmp(&other.priority)
+        Some(self.cmp(other))
     }
 }
 
-pub struct Game {
-    open: BinaryHeap<PrioritisedGameState>,
-    closed: HashSet<GameState>,
-}
+// A lower bound on the number of moves still needed to solve the game, for
+// the optimal search. Each term counts moves of a different kind, so no move
+// is counted twice:
+// - Every suit of dragons still in play needs a move to collect them.
+// - A column where a numbered card sits on top of a lower card of the same
+//   suit needs at least one move taking cards from it to another column or to
+//   a cell. The card on top can't go to the foundation before the lower one,
+//   and collecting dragons doesn't move it.
+// - Without automatic moves, every other card in play needs a move to the
+//   foundation.
+//
+// A single move changes each term by at most one, so the bound is also
+// consistent.
+fn lower_bound(state: &GameState, automatic_moves: AutomaticMoves) -> i32 {
+    let cards_in_play = || {
+        state
+            .columns
+            .iter()
+            .flatten()
+            .chain(&state.top_left_storage)
+    };
 
-impl Game {
-    pub fn new() -> Game {
-        Game {
-            open: BinaryHeap::new(),
-            closed: HashSet::new(),
-        }
-    }
+    let dragon_suits = [Suit::Red, Suit::Green, Suit::Black]
+        .into_iter()
+        .filter(|suit| cards_in_play().any(|card| card.is_dragon_with_suit(*suit)))
+        .count();
 
-    pub fn play(&mut self, state: GameState) -> Option<Vec<(GameState, GameMove)>> {
-        self.initialise(state);
+    let blocked_columns = state
+        .columns
+        .iter()
+        .filter(|column| {
+            // The lowest value seen so far for each suit, starting from the
+            // bottom of the column
+            let mut lowest = [u8::MAX; 4];
+            column.iter().any(|card| match card.value {
+                Some(value) => {
+                    let blocked = value > lowest[card.suit as usize];
+                    lowest[card.suit as usize] = lowest[card.suit as usize].min(value);
+                    blocked
+                }
+                None => false,
+            })
+        })
+        .count();
 
-        while !self.open.is_empty() {
-            let head = self.open.pop().unwrap();
-            let solution = self.expand_node(head);
-            if solution.is_some() {
-                return solution;
-            }
-        }
+    let foundation_moves = match automatic_moves {
+        AutomaticMoves::Emulated => 0,
+        AutomaticMoves::Manual => cards_in_play().filter(|card| card.value.is_some()).count(),
+    };
 
-        None
-    }
+    (dragon_suits + blocked_columns + foundation_moves) as i32
+}
 
-    fn heuristic(state: &GameState) -> i32 {
-        // comments are indicating what result we get if the code below the
-        // comment is removed
-        let mut score = 0;
+// A state in the search tree, linked to the state it was reached from. The
+// path to a state is found by following the parents back to the start, so
+// each node only stores the move that was made.
+struct Node {
+    parent: Option<usize>,
+    game_move: GameMove,
+}
 
-        // solution 0 length 173
-        // solution 1 length 120
-        // solution 2 length 275
+struct ProgressObserver {
+    interval: Duration,
+    callback: Box<dyn FnMut(&Progress) + Send>,
+}
 
-        // Collected cards are good
-        for value in state.top_right_storage {
-            score += value as i32;
-        }
+// A state reached from an expanded node, before it is checked against the
+// closed set
+struct Child {
+    state: GameState,
+    key: StateKey,
+    game_move: GameMove,
+    // The priority and tie break, see Expander::prioritise. Worked out by
+    // visit_node when it is missing.
+    priority: Option<(i32, i32)>,
+}
 
-        // solution 0 length 64
-        // solution 1 length 76
-        // solution 2 length 87
+#[derive(Default)]
+struct Expansion {
+    children: Vec<Child>,
+    automatic_moves: usize,
+    move_generation_time: Duration,
+    hashing_time: Duration,
+}
 
-        // Cards hidden by dragons are bad
-        let blocked_card_count = state
-            .columns
-            .iter()
-            .filter(|column| column.iter().any(|card| card.is_dragon()))
-            .fold(0i32, |acc, column| {
-                acc + i32::try_from(column.len()).expect(
-                    "Column lengths are bounded by the number of cards in \
-                    play, which is always small",
-                )
-            });
-        score -= blocked_card_count;
+// The part of the search that only reads the settings, so it can run on any
+// thread
+#[derive(Clone)]
+struct Expander {
+    automatic_moves: AutomaticMoves,
+    search_mode: SearchMode,
+    heuristic: Arc<dyn Heuristic>,
+}
 
-        score
+impl Expander {
+    // Makes the move, followed by any moves the game then makes by itself.
+    // Returns the number of moves the game made.
+    fn make_move(&self, state: &mut GameState, game_move: &GameMove) -> usize {
+        state.apply_unchecked(game_move);
+        match self.automatic_moves {
+            AutomaticMoves::Emulated => state.apply_automatic_moves().len(),
+            AutomaticMoves::Manual => 0,
+        }
     }
 
-    fn initialise(&mut self, state: GameState) {
-        assert!(self.open.is_empty());
-        assert!(self.closed.is_empty());
-
-        self.closed.insert(state.clone());
-        let new_entry = PrioritisedGameState {
-            priority: Self::heuristic(&state),
-            path: vec![state.clone()],
-            // NOTE: slight difference from python here. There is no zip longest
-            // in rust, so instead we initialise this vector with a default
-            // value. This makes it the same length as path, so that regular zip
-            // works.
-            moves: vec![GameMove::Start],
-            state,
-        };
-        self.open.push(new_entry)
-    }
-
-    fn visit_node(&mut self, parent: &PrioritisedGameState, state: GameState, game_move: GameMove) {
-        if !self.closed.contains(&state) {
-            self.closed.insert(state.clone());
-            // TODO this might work more efficiently using immutable collections
-            // from the im crate
-            //
-            // Even better, we could store a reference to the previous state and
-            // just iterate the resulting linked list to find the path
-            let new_entry = PrioritisedGameState {
-                priority: Self::heuristic(&state),
-                path: parent.path.iter().chain([&state]).cloned().collect(),
-                moves: parent.moves.iter().chain([&game_move]).cloned().collect(),
-                state,
-            };
-            self.open.push(new_entry);
+    fn prioritise(&self, state: &GameState, depth: usize) -> (i32, i32) {
+        match self.search_mode {
+            SearchMode::Greedy => (self.heuristic.score(state), 0),
+            // The heap returns the highest priority first, so negate the
+            // estimated solution length. Prefer the deepest state when the
+            // estimates are equal, it is the closest to a solution.
+            SearchMode::Optimal => {
+                let depth = depth as i32;
+                (-(depth + lower_bound(state, self.automatic_moves)), depth)
+            }
         }
     }
 
-    fn expand_node(&mut self, state: PrioritisedGameState) -> Option<Vec<(GameState, GameMove)>> {
+    // With prioritise, the children are scored here, on the thread doing the
+    // expansion. Otherwise they are scored later, and only if they are new.
+    fn expand(&self, state: &GameState, depth: usize, prioritise: bool) -> Expansion {
         // TODO we should make this more efficient by using a greedy algorithm
         //
         // That modification would expand a child node immediately if it has a
@@ -167,143 +306,455 @@ impl Game {
         // Could a generator function yielding the next move help here? That
         // would keep its state for the next call.
 
-        // the game forces us to move any cards to the top right storage if it's
-        // a valid move
-        //
-        // This is also our base case, as the game is won when all cards have
-        // been stacked here.
-        //
-        // The dragons might not all be collected yet, but that's fine, since if
-        // we have all the numbered cards, the dragons are always free to
-        // collect as the final move. This might also be automatic.
-        //
-        // TODO Does this also happen for cards in the top left storage?
-        //      In any case, they would be collected to the top right as soon as
-        //      they are moved away from the top left, there is no point keeping
-        //      them there.
-        //
+        let generation_start = Instant::now();
+        let moves = state.legal_moves();
+        let mut expansion = Expansion {
+            children: Vec::with_capacity(moves.len()),
+            move_generation_time: generation_start.elapsed(),
+            ..Expansion::default()
+        };
 
-        // TODO could move this after we make a move to spot the win 1 iteration
-        //      sooner
+        // test out every possible move
+        for game_move in moves {
+            let generation_start = Instant::now();
+            let mut child = state.clone();
+            expansion.automatic_moves += self.make_move(&mut child, &game_move);
+            expansion.move_generation_time += generation_start.elapsed();
 
-        if state.state.is_solved() {
-            return Some(zip(state.path, state.moves).clone().collect());
+            let hashing_start = Instant::now();
+            let key = StateKey::from(&child);
+            expansion.hashing_time += hashing_start.elapsed();
+
+            let priority = prioritise.then(|| self.prioritise(&child, depth + 1));
+            expansion.children.push(Child {
+                state: child,
+                key,
+                game_move,
+                priority,
+            });
         }
 
-        // Use a copy so we can reset the state after each move
-        let mut state_copy = state.state.clone();
+        expansion
+    }
+}
 
-        for i in 0..8 {
-            if state_copy.can_move_column_to_top_right_storage(i) {
-                state_copy.move_column_to_top_right_storage(i);
-                self.visit_node(
-                    &state,
-                    state_copy,
-                    GameMove::ColumnToTopRightStorage { column: i },
-                );
+pub struct Game {
+    open: BinaryHeap<PrioritisedGameState>,
+    // The number of moves to the best known path to each state
+    closed: HashMap<StateKey, usize>,
+    // Every state pushed to the open set. Nodes are never removed, the open
+    // set and the solutions refer to them by index.
+    nodes: Vec<Node>,
+    // The position the search started from, before any automatic moves. The
+    // states on a path are rebuilt from it.
+    start: Option<GameState>,
+    expander: Expander,
+    threads: usize,
+    limits: Limits,
+    cancellation: Option<CancellationToken>,
+    progress: Option<ProgressObserver>,
+    stats: SearchStats,
+    // The shortest solution seen while generating nodes. The search may stop
+    // before it is expanded. Holds the depth and the node.
+    best_solution: Option<(usize, usize)>,
+}
 
-                // We have to make this move, the game won't let us do anything
-                // else. If it results in a losing game, then we need to
-                // backtrack
-                //
-                // TODO there might be some edge cases where the game doesn't
-                // force you to make this move. Those could be the states where
-                // this move actually makes you lose the game.
-                //
-                // E.G. when moving the card would mean a free columns stays
-                // blocked by another card.
-                return None;
-            }
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Game {
+    pub fn new() -> Game {
+        Game {
+            open: BinaryHeap::new(),
+            closed: HashMap::new(),
+            nodes: Vec::new(),
+            start: None,
+            expander: Expander {
+                automatic_moves: AutomaticMoves::default(),
+                search_mode: SearchMode::default(),
+                heuristic: Arc::new(DefaultHeuristic),
+            },
+            threads: 1,
+            limits: Limits::default(),
+            cancellation: None,
+            progress: None,
+            stats: SearchStats::default(),
+            best_solution: None,
         }
+    }
 
-        for i in 0..3 {
-            if state_copy.can_move_top_left_to_top_right_storage(i) {
-                state_copy.move_top_left_to_top_right_storage(i);
-                self.visit_node(
-                    &state,
-                    state_copy,
-                    GameMove::TopLeftToTopRightStorage { top_left_index: i },
-                );
+    pub fn with_automatic_moves(mut self, automatic_moves: AutomaticMoves) -> Game {
+        self.expander.automatic_moves = automatic_moves;
+        self
+    }
 
-                // See comment above
-                return None;
-            }
+    pub fn with_search_mode(mut self, search_mode: SearchMode) -> Game {
+        self.expander.search_mode = search_mode;
+        self
+    }
+
+    // Only used by the greedy search
+    pub fn with_heuristic(mut self, heuristic: impl Heuristic + 'static) -> Game {
+        self.expander.heuristic = Arc::new(heuristic);
+        self
+    }
+
+    // Expands nodes on this many threads. The search works in rounds, taking
+    // the best node for each thread from the open set, expanding them at the
+    // same time, and then adding the children in the order the nodes were
+    // taken. So the result only depends on the number of threads, and not on
+    // how the threads are scheduled. One thread, the default, searches one
+    // node at a time without starting any threads.
+    //
+    // The optimal search still finds a shortest solution, as a solution is
+    // only accepted when it is the best node in the open set.
+    pub fn with_threads(mut self, threads: usize) -> Game {
+        assert!(threads > 0, "The search needs at least one thread");
+        self.threads = threads;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Game {
+        self.limits = limits;
+        self
+    }
+
+    // Cancelling the token stops the search with Limit::Cancelled
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Game {
+        self.cancellation = Some(token);
+        self
+    }
+
+    // Calls the callback with the search progress, at most once per interval.
+    // The callback runs on the searching thread, so it should return quickly.
+    pub fn with_progress(
+        mut self,
+        interval: Duration,
+        callback: impl FnMut(&Progress) + Send + 'static,
+    ) -> Game {
+        self.progress = Some(ProgressObserver {
+            interval,
+            callback: Box::new(callback),
+        });
+        self
+    }
+
+    // See Expander::make_move
+    fn make_move(&self, state: &mut GameState, game_move: &GameMove) -> usize {
+        self.expander.make_move(state, game_move)
+    }
+
+    pub fn play(&mut self, state: GameState) -> Option<Vec<(GameState, GameMove)>> {
+        self.solve(state).solution().map(|solution| solution.steps)
+    }
+
+    pub fn solve(&mut self, state: GameState) -> SolveResult {
+        self.solve_with_stats(state).0
+    }
+
+    pub fn solve_with_stats(&mut self, state: GameState) -> (SolveResult, SearchStats) {
+        if let Err(violations) = state.validate() {
+            self.stats = SearchStats::default();
+            return (SolveResult::InvalidStart(violations), self.stats.clone());
         }
 
-        // test out every possible move. The list of all moves are:
-        // collect dragons
-        for suit in [Suit::Red, Suit::Green, Suit::Black] {
-            if state_copy.can_collect_dragons(suit) {
-                state_copy.collect_dragons(suit);
-                self.visit_node(&state, state_copy, GameMove::CollectDragons { suit });
+        let start_time = Instant::now();
+        let result = self.search(state, start_time);
+        self.stats.total_time = start_time.elapsed();
+        (result, self.stats.clone())
+    }
 
-                state_copy = state.state.clone();
-            }
+    fn search(&mut self, state: GameState, start_time: Instant) -> SolveResult {
+        self.initialise(state);
+        let expander = self.expander.clone();
+
+        if self.threads == 1 {
+            return self.run(start_time, |round| {
+                round
+                    .iter()
+                    .map(|(state, depth)| expander.expand(state, *depth, false))
+                    .collect()
+            });
         }
 
-        // move any set of cards from any column to any other column
-        for from_column_index in 0..8 {
-            for to_column_index in 0..8 {
-                for stack_size in (1..9 + 1).rev() {
-                    if state_copy.can_move_column_to_other_column(MoveColumnParameters {
-                        from_column_index,
-                        to_column_index,
-                        stack_size,
-                    }) {
-                        state_copy.move_column_to_other_column(MoveColumnParameters {
-                            from_column_index,
-                            to_column_index,
-                            stack_size,
+        let (job_sender, job_receiver) = channel::<(usize, GameState, usize)>();
+        let job_receiver = Mutex::new(job_receiver);
+        let (expansion_sender, expansion_receiver) = channel();
+
+        thread::scope(|scope| {
+            // Owned by this closure, so that a panic below drops it on the way
+            // out and the threads stop before the scope waits for them
+            let job_sender = job_sender;
+
+            for _ in 0..self.threads {
+                let job_receiver = &job_receiver;
+                let expansion_sender = expansion_sender.clone();
+                let expander = &expander;
+                scope.spawn(move || loop {
+                    let job = job_receiver.lock().unwrap().recv();
+                    // The search has finished
+                    let Ok((index, state, depth)) = job else {
+                        break;
+                    };
+                    // A panic is passed on to the search loop, which would
+                    // otherwise wait for this expansion forever
+                    let expansion = panic::catch_unwind(AssertUnwindSafe(|| {
+                        expander.expand(&state, depth, true)
+                    }));
+                    let stop = expansion.is_err();
+                    if expansion_sender.send((index, expansion)).is_err() || stop {
+                        break;
+                    }
+                });
+            }
+            // Only the threads can send, so recv fails if they have all
+            // stopped
+            drop(expansion_sender);
+
+            self.run(start_time, |round| {
+                let count = round.len();
+                for (index, (state, depth)) in round.into_iter().enumerate() {
+                    job_sender.send((index, state, depth)).unwrap();
+                }
+
+                let mut expansions: Vec<(usize, Expansion)> = (0..count)
+                    .map(|_| {
+                        let (index, expansion) =
+                            expansion_receiver.recv().expect("A search thread stopped");
+                        match expansion {
+                            Ok(expansion) => (index, expansion),
+                            Err(payload) => panic::resume_unwind(payload),
+                        }
+                    })
+                    .collect();
+                expansions.sort_by_key(|(index, _)| *index);
+                expansions
+                    .into_iter()
+                    .map(|(_, expansion)| expansion)
+                    .collect()
+            })
+            // Dropping job_sender stops the threads
+        })
+    }
+
+    // The search loop. expand takes the states and depths of the nodes picked
+    // in a round, and returns their expansions in the same order.
+    fn run(
+        &mut self,
+        start_time: Instant,
+        mut expand: impl FnMut(Vec<(GameState, usize)>) -> Vec<Expansion>,
+    ) -> SolveResult {
+        let mut last_report = start_time;
+        let mut best_priority = i32::MIN;
+
+        loop {
+            let mut round = vec![];
+
+            while round.len() < self.threads {
+                let Some(head) = self.open.pop() else {
+                    break;
+                };
+
+                // A shorter path to this state was found after it was queued
+                if self.closed[&head.key] < head.depth {
+                    continue;
+                }
+
+                // The nodes already picked for this round come first. One of
+                // them might lead to a shorter solution.
+                if head.state.is_solved() && !round.is_empty() {
+                    self.open.push(head);
+                    break;
+                }
+
+                if let Some(result) = self.limit_reached() {
+                    return result;
+                }
+                self.stats.expanded_nodes += 1;
+                best_priority = best_priority.max(head.priority);
+
+                if let Some(progress) = &mut self.progress {
+                    let now = Instant::now();
+                    if now - last_report >= progress.interval {
+                        last_report = now;
+                        (progress.callback)(&Progress {
+                            expanded_nodes: self.stats.expanded_nodes,
+                            open_states: self.open.len(),
+                            stored_states: self.closed.len(),
+                            best_priority,
+                            elapsed: now - start_time,
                         });
-                        self.visit_node(
-                            &state,
-                            state_copy,
-                            GameMove::ColumnToOtherColumn {
-                                from_column_index,
-                                to_column_index,
-                                stack_size,
-                            },
-                        );
-                        state_copy = state.state.clone();
                     }
                 }
+
+                // The base case, the game is won when all cards have been
+                // stacked in the top right storage.
+                //
+                // The dragons might not all be collected yet, but that's fine,
+                // since if we have all the numbered cards, the dragons are
+                // always free to collect as the final move.
+                //
+                // Cards the game moves to the top right storage by itself are
+                // handled in make_move, see apply_automatic_moves. Any other
+                // move to the top right storage is up to the player, and is
+                // searched like every other move, since keeping a card in play
+                // can be what wins the game.
+
+                // TODO could move this after we make a move to spot the win 1
+                //      iteration sooner
+
+                if head.state.is_solved() {
+                    return SolveResult::Solved(Solution {
+                        steps: self.path(head.node),
+                        proven_optimal: self.expander.search_mode == SearchMode::Optimal,
+                    });
+                }
+
+                round.push(head);
             }
-        }
 
-        // move a card from the centre to the storage area
-        for column_index in 0..8 {
-            if state_copy.can_move_column_to_top_left(column_index) {
-                state_copy.move_column_to_top_left(column_index);
-                self.visit_node(
-                    &state,
-                    state_copy,
-                    GameMove::ToTopLeftStorage {
-                        column: column_index,
-                    },
-                );
-                state_copy = state.state.clone();
+            if round.is_empty() {
+                return SolveResult::ProvenUnsolvable;
             }
-        }
 
-        // move a card out of the top left storage area to a column
-        for top_left_index in 0..3 {
-            for column_index in 0..8 {
-                if state_copy.can_move_top_left_to_column(top_left_index, column_index) {
-                    state_copy.move_top_left_to_column(top_left_index, column_index);
-                    self.visit_node(
-                        &state,
-                        state_copy,
-                        GameMove::TopLeftToColumn {
-                            top_left_index,
-                            column_index,
-                        },
-                    );
-                    state_copy = state.state.clone()
+            let parents: Vec<(usize, usize)> =
+                round.iter().map(|head| (head.depth, head.node)).collect();
+            let expansions = expand(
+                round
+                    .into_iter()
+                    .map(|head| (head.state, head.depth))
+                    .collect(),
+            );
+
+            for ((depth, node), expansion) in zip(parents, expansions) {
+                self.stats.automatic_moves += expansion.automatic_moves;
+                self.stats.move_generation_time += expansion.move_generation_time;
+                self.stats.hashing_time += expansion.hashing_time;
+                for child in expansion.children {
+                    self.stats.generated_nodes += 1;
+                    self.visit_node(depth, node, child);
                 }
             }
         }
+    }
+
+    fn limit_reached(&self) -> Option<SolveResult> {
+        let cancelled = self
+            .cancellation
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled());
+        let limit = if cancelled {
+            Some(Limit::Cancelled)
+        } else {
+            self.limits
+                .reached(self.stats.expanded_nodes, self.closed.len())
+        }?;
+
+        Some(SolveResult::LimitReached {
+            limit,
+            best: self.best_solution.map(|(_, node)| Solution {
+                steps: self.path(node),
+                proven_optimal: false,
+            }),
+        })
+    }
+
+    // The steps from the start to the node, in the same form as the result of
+    // play. Only the moves are stored, so the states are made again.
+    fn path(&self, node: usize) -> Vec<(GameState, GameMove)> {
+        let mut moves = vec![];
+        let mut current = Some(node);
+        while let Some(index) = current {
+            moves.push(&self.nodes[index].game_move);
+            current = self.nodes[index].parent;
+        }
+
+        let mut state = self.start.clone().expect("The search has started");
+        moves
+            .into_iter()
+            .rev()
+            .map(|game_move| {
+                self.make_move(&mut state, game_move);
+                (state.clone(), game_move.clone())
+            })
+            .collect()
+    }
+
+    fn initialise(&mut self, mut state: GameState) {
+        assert!(self.open.is_empty());
+        assert!(self.closed.is_empty());
+
+        self.stats = SearchStats::default();
+        self.start = Some(state.clone());
+        // The game may already move some cards while dealing
+        self.stats.automatic_moves = self.make_move(&mut state, &GameMove::Start);
+
+        let key = StateKey::from(&state);
+        self.closed.insert(key, 0);
+        if state.is_solved() {
+            self.best_solution = Some((0, 0));
+        }
+        self.nodes.push(Node {
+            parent: None,
+            game_move: GameMove::Start,
+        });
+        let (priority, tie_break) = self.expander.prioritise(&state, 0);
+        self.open.push(PrioritisedGameState {
+            priority,
+            tie_break,
+            state,
+            key,
+            depth: 0,
+            node: 0,
+        });
+        self.stats.peak_open_states = 1;
+    }
+
+    fn visit_node(&mut self, parent_depth: usize, parent: usize, child: Child) {
+        let depth = parent_depth + 1;
+        let hashing_start = Instant::now();
+        if let Some(previous_depth) = self.closed.get(&child.key) {
+            // The optimal search has to look at a state again if it found a
+            // shorter path to it
+            if self.expander.search_mode == SearchMode::Greedy || *previous_depth <= depth {
+                self.stats.duplicates += 1;
+                self.stats.hashing_time += hashing_start.elapsed();
+                return;
+            }
+        }
 
-        None
+        self.closed.insert(child.key, depth);
+        self.stats.hashing_time += hashing_start.elapsed();
+        self.stats.max_depth = self.stats.max_depth.max(depth);
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            parent: Some(parent),
+            game_move: child.game_move,
+        });
+
+        if child.state.is_solved()
+            && self
+                .best_solution
+                .is_none_or(|(best_depth, _)| best_depth > depth)
+        {
+            self.best_solution = Some((depth, node));
+        }
+
+        let (priority, tie_break) = child
+            .priority
+            .unwrap_or_else(|| self.expander.prioritise(&child.state, depth));
+        self.open.push(PrioritisedGameState {
+            priority,
+            tie_break,
+            state: child.state,
+            key: child.key,
+            depth,
+            node,
+        });
+        self.stats.peak_open_states = self.stats.peak_open_states.max(self.open.len());
     }
 }
 
@@ -311,8 +762,12 @@ impl Game {
 mod test {
     use super::*;
     use crate::card::Suit::{FaceDown, Red};
-    use googletest::assert_that;
+    use crate::deal::deal;
+    use crate::verify::verify_solution;
     use googletest::matchers::*;
+    use googletest::{assert_that, matches_pattern};
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 
     fn solved() -> GameState {
         GameState {
@@ -383,7 +838,7 @@ mod test {
         assert_that!(almost_solved.is_solved(), eq(false));
 
         // Solved after a single iteration
-        let mut game = Game::new();
+        let mut game = Game::new().with_automatic_moves(AutomaticMoves::Manual);
         let result = game.play(almost_solved.clone());
         assert_that!(result.is_none(), eq(false));
         let result = result.unwrap();
@@ -391,4 +846,348 @@ mod test {
         assert_that!(&result[0].0, eq(&almost_solved));
         assert_that!(result.last().unwrap().0.is_solved(), eq(true));
     }
+
+    #[test]
+    fn test_automatic_move_solve() {
+        // The game moves the last card by itself, so there is nothing left for
+        // the player to do
+        let result = Game::new().play(almost_solved()).unwrap();
+        assert_that!(result.len(), eq(1));
+        assert_that!(result[0].0.is_solved(), eq(true));
+    }
+
+    #[test]
+    fn test_solutions_verify() {
+        for deal in [
+            include_str!("../deals/deal0.txt"),
+            include_str!("../deals/deal1.txt"),
+            include_str!("../deals/deal2.txt"),
+        ] {
+            let start: GameState = deal.parse().unwrap();
+            let solution = Game::new().play(start.clone()).unwrap();
+            assert_that!(
+                verify_solution(&start, &solution, AutomaticMoves::Emulated),
+                ok(anything())
+            );
+        }
+    }
+
+    // The fewest moves needed to solve the game, by breadth first search over
+    // the exact states, without any of the solver's pruning
+    fn shortest_solution(start: &GameState, automatic_moves: AutomaticMoves) -> Option<usize> {
+        let game = Game::new().with_automatic_moves(automatic_moves);
+        let mut start = start.clone();
+        game.make_move(&mut start, &GameMove::Start);
+
+        let mut seen = HashSet::from([start.notation().to_string()]);
+        let mut frontier = vec![start];
+        for depth in 0.. {
+            if frontier.is_empty() {
+                return None;
+            }
+            if frontier.iter().any(|state| state.is_solved()) {
+                return Some(depth);
+            }
+
+            let mut next = vec![];
+            for state in frontier {
+                for game_move in state.legal_moves() {
+                    let mut child = state.clone();
+                    game.make_move(&mut child, &game_move);
+                    if seen.insert(child.notation().to_string()) {
+                        next.push(child);
+                    }
+                }
+            }
+            frontier = next;
+        }
+        unreachable!()
+    }
+
+    // Positions close to the end of the greedy solutions for the deals
+    fn endgames(moves_left: usize) -> Vec<GameState> {
+        [
+            include_str!("../deals/deal0.txt"),
+            include_str!("../deals/deal1.txt"),
+        ]
+        .iter()
+        .map(|deal| {
+            let solution = Game::new().play(deal.parse().unwrap()).unwrap();
+            solution[solution.len() - 1 - moves_left].0.clone()
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_optimal_solve() {
+        // Without automatic moves, the breadth first search is too slow here
+        for start in endgames(4) {
+            let shortest = shortest_solution(&start, AutomaticMoves::Emulated);
+
+            for threads in [1, 3] {
+                let solution = Game::new()
+                    .with_search_mode(SearchMode::Optimal)
+                    .with_threads(threads)
+                    .solve(start.clone())
+                    .solution()
+                    .unwrap();
+
+                assert_that!(solution.proven_optimal, eq(true));
+                assert_that!(Some(solution.move_count()), eq(shortest));
+                assert_that!(
+                    verify_solution(&start, &solution.steps, AutomaticMoves::Emulated),
+                    ok(anything())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_lower_bound_is_admissible() {
+        for automatic_moves in [AutomaticMoves::Emulated, AutomaticMoves::Manual] {
+            for start in endgames(5) {
+                let steps = Game::new()
+                    .with_automatic_moves(automatic_moves)
+                    .with_search_mode(SearchMode::Optimal)
+                    .play(start)
+                    .unwrap();
+
+                for (i, (state, _)) in steps.iter().enumerate() {
+                    let moves_left = (steps.len() - 1 - i) as i32;
+                    assert_that!(lower_bound(state, automatic_moves), le(moves_left));
+                }
+            }
+        }
+
+        let state: GameState = GameState::parse_unchecked(
+            "cells: GD\n\
+             foundation: F1 R0 G0 B0\n\
+             1: R1 R2\n\
+             2: G1 B2 G3\n\
+             3: GD GD GD\n\
+             4: RD RD RD RD\n\
+             5: B1 G2 R3\n\
+             6: BD BD BD BD\n\
+             7:\n\
+             8:\n",
+        )
+        .unwrap();
+        // Three suits of dragons and the first two columns
+        assert_that!(lower_bound(&state, AutomaticMoves::Emulated), eq(5));
+        // Plus the eight numbered cards
+        assert_that!(lower_bound(&state, AutomaticMoves::Manual), eq(13));
+    }
+
+    #[test]
+    fn test_limits() {
+        let start: GameState = include_str!("../deals/deal1.txt").parse().unwrap();
+        let result = Game::new()
+            .with_search_mode(SearchMode::Optimal)
+            .with_limits(Limits {
+                max_expanded_nodes: Some(10),
+                ..Limits::default()
+            })
+            .solve(start.clone());
+        // Too few nodes to find any solution, let alone prove it is optimal
+        assert_that!(
+            matches!(
+                result,
+                SolveResult::LimitReached {
+                    limit: Limit::ExpandedNodes,
+                    best: None
+                }
+            ),
+            eq(true)
+        );
+
+        let mut game = Game::new().with_limits(Limits {
+            max_stored_states: Some(500),
+            ..Limits::default()
+        });
+        let result = game.solve(start);
+        assert_that!(
+            matches!(
+                result,
+                SolveResult::LimitReached {
+                    limit: Limit::StoredStates,
+                    ..
+                }
+            ),
+            eq(true)
+        );
+        // Only goes over by the children of the last node expanded
+        assert_that!(game.closed.len(), lt(600));
+
+        let result = Game::new()
+            .with_limits(Limits {
+                max_expanded_nodes: Some(1000),
+                ..Limits::default()
+            })
+            .solve(almost_solved());
+        assert_that!(matches!(result, SolveResult::Solved(_)), eq(true));
+        // Only an optimal search proves optimality
+        assert_that!(result.solution().unwrap().proven_optimal, eq(false));
+    }
+
+    #[test]
+    fn test_proven_unsolvable() {
+        let result = Game::new().solve(deal(9));
+        assert_that!(matches!(result, SolveResult::ProvenUnsolvable), eq(true));
+
+        let result = Game::new().with_threads(2).solve(deal(9));
+        assert_that!(matches!(result, SolveResult::ProvenUnsolvable), eq(true));
+    }
+
+    #[test]
+    fn test_invalid_start() {
+        let mut state = deal(0);
+        state.columns[0].push(Card {
+            suit: FaceDown,
+            value: None,
+        });
+
+        let (result, stats) = Game::new().solve_with_stats(state);
+        assert_that!(
+            result,
+            matches_pattern!(SolveResult::InvalidStart(not(empty())))
+        );
+        assert_that!(stats.expanded_nodes, eq(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Heuristic failed")]
+    fn test_thread_panic() {
+        let calls = AtomicUsize::new(0);
+        let heuristic = move |state: &GameState| {
+            if calls.fetch_add(1, AtomicOrdering::Relaxed) == 50 {
+                panic!("Heuristic failed");
+            }
+            DefaultHeuristic.score(state)
+        };
+
+        Game::new()
+            .with_heuristic(heuristic)
+            .with_threads(2)
+            .solve(deal(0));
+    }
+
+    #[test]
+    fn test_threads() {
+        let moves = |steps: &[(GameState, GameMove)]| -> Vec<GameMove> {
+            steps
+                .iter()
+                .map(|(_, game_move)| game_move.clone())
+                .collect()
+        };
+
+        for deal in [
+            include_str!("../deals/deal0.txt"),
+            include_str!("../deals/deal1.txt"),
+        ] {
+            let start: GameState = deal.parse().unwrap();
+
+            for threads in [2, 4] {
+                let (result, stats) = Game::new()
+                    .with_threads(threads)
+                    .solve_with_stats(start.clone());
+                let steps = result.solution().unwrap().steps;
+                assert_that!(
+                    verify_solution(&start, &steps, AutomaticMoves::Emulated),
+                    ok(anything())
+                );
+
+                // The same number of threads always gives the same search
+                let (again, again_stats) = Game::new()
+                    .with_threads(threads)
+                    .solve_with_stats(start.clone());
+                assert_that!(moves(&again.solution().unwrap().steps), eq(moves(&steps)));
+                assert_that!(again_stats.expanded_nodes, eq(stats.expanded_nodes));
+                assert_that!(again_stats.generated_nodes, eq(stats.generated_nodes));
+            }
+        }
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let start: GameState = include_str!("../deals/deal1.txt").parse().unwrap();
+        let result = Game::new().with_cancellation(token).solve(start);
+        assert_that!(
+            matches!(
+                result,
+                SolveResult::LimitReached {
+                    limit: Limit::Cancelled,
+                    best: None
+                }
+            ),
+            eq(true)
+        );
+
+        // From another thread, while the search is running
+        let token = CancellationToken::new();
+        let mut game = Game::new()
+            .with_search_mode(SearchMode::Optimal)
+            .with_cancellation(token.clone());
+        let start: GameState = include_str!("../deals/deal2.txt").parse().unwrap();
+        let search = std::thread::spawn(move || game.solve(start));
+        std::thread::sleep(Duration::from_millis(50));
+        token.cancel();
+
+        assert_that!(
+            matches!(
+                search.join().unwrap(),
+                SolveResult::LimitReached {
+                    limit: Limit::Cancelled,
+                    ..
+                }
+            ),
+            eq(true)
+        );
+    }
+
+    #[test]
+    fn test_progress() {
+        let reports = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let sink = reports.clone();
+        let start: GameState = include_str!("../deals/deal1.txt").parse().unwrap();
+        let steps = Game::new()
+            .with_progress(Duration::ZERO, move |progress| {
+                sink.lock().unwrap().push(progress.clone())
+            })
+            .play(start)
+            .unwrap();
+
+        let reports = reports.lock().unwrap();
+        assert_that!(reports.is_empty(), eq(false));
+        for (i, progress) in reports.iter().enumerate() {
+            // A report for every node with a zero interval
+            assert_that!(progress.expanded_nodes, eq(i + 1));
+            assert_that!(progress.stored_states, ge(progress.open_states));
+            assert_that!(progress.best_priority, ge(reports[0].best_priority));
+        }
+        assert_that!(reports.len(), ge(steps.len()));
+    }
+
+    #[test]
+    fn test_stats() {
+        let start: GameState = include_str!("../deals/deal1.txt").parse().unwrap();
+        let mut game = Game::new();
+        let (result, stats) = game.solve_with_stats(start);
+        let solution = result.solution().unwrap();
+
+        // Every generated node is either a duplicate or a new state
+        assert_that!(
+            stats.generated_nodes - stats.duplicates + 1,
+            eq(game.closed.len())
+        );
+        assert_that!(stats.expanded_nodes, le(game.closed.len()));
+        assert_that!(stats.max_depth, ge(solution.move_count()));
+        assert_that!(stats.peak_open_states, ge(game.open.len()));
+        assert_that!(stats.automatic_moves, gt(0));
+        assert_that!(
+            stats.move_generation_time + stats.hashing_time,
+            le(stats.total_time)
+        );
+    }
 }
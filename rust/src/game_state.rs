@@ -1,8 +1,7 @@
 use crate::card::Suit::FaceDown;
 use crate::card::*;
-use std::fmt::{write, Formatter};
+use std::cmp;
 use std::hash::{Hash, Hasher};
-use std::{cmp, fmt};
 
 #[derive(Debug, Clone, Eq)]
 pub struct GameState {
@@ -28,7 +27,7 @@ impl GameState {
     // All the columns in the centre have no cards
     pub fn is_solved(&self) -> bool {
         for column in &self.columns {
-            if column.len() != 0 {
+            if !column.is_empty() {
                 return false;
             }
         }
@@ -53,7 +52,7 @@ impl GameState {
     // TODO Suit.SPECIAL card can always be moved to storage, it's hardcoded to
     // have value of 1 for now
     pub fn can_move_column_to_top_right_storage(&self, column_index: usize) -> bool {
-        if self.columns[column_index].len() == 0 {
+        if self.columns[column_index].is_empty() {
             return false;
         }
 
@@ -61,13 +60,13 @@ impl GameState {
             .last()
             .expect("Size was already check to be non-zero");
 
-        match { card.value } {
+        match card.value {
             None => false,
             Some(value) => value == 1 || self.top_right_storage[card.suit as usize] == value - 1,
         }
     }
 
-    pub fn move_column_to_top_right_storage(&mut self, column_index: usize) {
+    pub(crate) fn move_column_to_top_right_storage(&mut self, column_index: usize) {
         let card = self.columns[column_index].pop().unwrap();
         self.top_right_storage[card.suit as usize] = card.value.unwrap()
     }
@@ -79,13 +78,13 @@ impl GameState {
 
         let card = &self.top_left_storage[top_left_index];
 
-        match { card.value } {
+        match card.value {
             None => false,
             Some(value) => self.top_right_storage[card.suit as usize] == value - 1,
         }
     }
 
-    pub fn move_top_left_to_top_right_storage(&mut self, top_left_index: usize) {
+    pub(crate) fn move_top_left_to_top_right_storage(&mut self, top_left_index: usize) {
         let card = self.top_left_storage.remove(top_left_index);
         self.top_right_storage[card.suit as usize] = card
             .value
@@ -118,7 +117,7 @@ impl GameState {
         card_to_move.can_be_moved_on_top_of(target_card)
     }
 
-    pub fn move_top_left_to_column(&mut self, top_left_index: usize, column_index: usize) {
+    pub(crate) fn move_top_left_to_column(&mut self, top_left_index: usize, column_index: usize) {
         self.columns[column_index].push(self.top_left_storage.remove(top_left_index))
     }
 
@@ -126,7 +125,7 @@ impl GameState {
         !self.columns[column_index].is_empty() && self.top_left_storage.len() < 3
     }
 
-    pub fn move_column_to_top_left(&mut self, column_index: usize) {
+    pub(crate) fn move_column_to_top_left(&mut self, column_index: usize) {
         self.top_left_storage
             .push(self.columns[column_index].pop().unwrap());
         assert!(self.top_left_storage.len() <= 3);
@@ -162,7 +161,7 @@ impl GameState {
         free_dragon_count == 4
     }
 
-    pub fn collect_dragons(&mut self, suit: Suit) {
+    pub(crate) fn collect_dragons(&mut self, suit: Suit) {
         // This is always called after checking if this move is valid.
         // Therefore, we can just remove all the dragons and add a face down
         // card to the top left.
@@ -180,7 +179,7 @@ impl GameState {
         assert!(self.top_left_storage.len() <= 3)
     }
 
-    fn get_column_stack_size(&self, column_index: usize) -> usize {
+    pub(crate) fn get_column_stack_size(&self, column_index: usize) -> usize {
         if self.columns[column_index].is_empty() {
             return 0;
         }
@@ -228,10 +227,10 @@ impl GameState {
         let column = &self.columns[p.from_column_index];
         let stack_first_card = &column[column.len() - p.stack_size];
         let target_card = self.columns[p.to_column_index].last().unwrap();
-        return stack_first_card.can_be_moved_on_top_of(target_card);
+        stack_first_card.can_be_moved_on_top_of(target_card)
     }
 
-    pub fn move_column_to_other_column(&mut self, p: MoveColumnParameters) {
+    pub(crate) fn move_column_to_other_column(&mut self, p: MoveColumnParameters) {
         assert_ne!(p.from_column_index, p.to_column_index);
         let mid = cmp::max(p.from_column_index, p.to_column_index);
         let (left, right) = self.columns.split_at_mut(mid);
@@ -253,115 +252,48 @@ impl GameState {
     }
 }
 
+// The columns are interchangeable, and so are the cells, so positions that
+// only differ in their order are the same position. Each position has one
+// canonical form, with the cells and the columns sorted, and equality and
+// hashing both go through it. Moving a stack to another empty column gives an
+// equal position, so the search doesn't explore it again.
 impl PartialEq<Self> for GameState {
     fn eq(&self, other: &Self) -> bool {
-        let mut top_left_storage = self.top_left_storage.clone();
-        top_left_storage.sort();
-        let mut other_top_left_storage = self.top_left_storage.clone();
-        other_top_left_storage.sort();
-
-        // columns are sorted by the bottom card to try to prevent useless
-        // moves moving stacks to another empty column
-        let get_bottom_card = |col: &Vec<Card>| {
-            *col.first().unwrap_or(&Card {
-                suit: Suit::Special,
-                value: None,
-            })
-        };
-        let bottom_card_sort =
-            |a: &Vec<Card>, b: &Vec<Card>| get_bottom_card(a).cmp(&get_bottom_card(b));
-        let mut columns = self.columns.clone();
-        columns.sort_by(bottom_card_sort);
-        let mut other_columns = other.columns.clone();
-        other_columns.sort_by(bottom_card_sort);
-
-        top_left_storage == other_top_left_storage
-            && columns == other_columns
-            && self.top_right_storage == other.top_right_storage
+        self.top_right_storage == other.top_right_storage
+            && self.canonical_order() == other.canonical_order()
     }
 }
 
 impl Hash for GameState {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        let mut top_left_storage = self.top_left_storage.clone();
-        top_left_storage.sort();
-
-        // columns are sorted by the bottom card to try to prevent useless
-        // moves moving stacks to another empty column
-        let get_bottom_card = |col: &Vec<Card>| {
-            *col.first().unwrap_or(&Card {
-                suit: Suit::Special,
-                value: None,
-            })
-        };
-        let bottom_card_sort =
-            |a: &Vec<Card>, b: &Vec<Card>| get_bottom_card(a).cmp(&get_bottom_card(b));
-        let mut columns = self.columns.clone();
-        columns.sort_by(bottom_card_sort);
-
+        let (top_left_storage, columns) = self.canonical_order();
         top_left_storage.hash(state);
         self.top_right_storage.hash(state);
         columns.hash(state);
     }
 }
 
-impl fmt::Display for GameState {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let mut top_row = String::from("========== GAME STATE =========\n");
-
-        let mut top_left_sorted = self.top_left_storage.clone();
-        top_left_sorted.sort();
-
-        for (_, card) in top_left_sorted.iter().enumerate() {
-            write(&mut top_row, format_args!("{} ", card))?;
-        }
-
-        for _ in 0..3 - self.top_left_storage.len() + 1 {
-            top_row += "    ";
-        }
-
-        for (suit, value) in self.top_right_storage.iter().enumerate() {
-            let value = *value;
-
-            if value == 0 {
-                top_row += "   "
-            } else {
-                write(
-                    &mut top_row,
-                    format_args!(
-                        "{} ",
-                        Card {
-                            suit: Suit::try_from(suit).unwrap(),
-                            value: Some(value)
-                        }
-                    ),
-                )?;
-            }
-        }
-
-        let mut columns = String::new();
-
-        let longest_row_len = self.columns.iter().fold(0, |max, v| cmp::max(max, v.len()));
-
-        for row_index in 0..longest_row_len {
-            for column in self.columns.iter() {
-                let card = column.get(row_index);
-                match card {
-                    None => {
-                        columns += "   ";
-                    }
-                    Some(card) => {
-                        columns += card.to_string().as_str();
-                        columns += " ";
-                    }
-                }
-            }
-            columns += "\n";
+impl GameState {
+    // The same position, with the cells and the columns in sorted order.
+    // Equal positions have identical canonical forms.
+    pub fn canonical(&self) -> GameState {
+        let (top_left_storage, columns) = self.canonical_order();
+        let mut columns = columns.into_iter().cloned();
+        GameState {
+            top_left_storage,
+            top_right_storage: self.top_right_storage,
+            columns: std::array::from_fn(|_| columns.next().unwrap()),
         }
+    }
 
-        top_row += "\n";
-        top_row += columns.as_str();
-        f.write_str(top_row.as_str())
+    // The cells and the columns of the canonical form, without copying the
+    // columns
+    pub(crate) fn canonical_order(&self) -> (Vec<Card>, [&Vec<Card>; 8]) {
+        let mut top_left_storage = self.top_left_storage.clone();
+        top_left_storage.sort();
+        let mut columns = self.columns.each_ref();
+        columns.sort();
+        (top_left_storage, columns)
     }
 }
 
@@ -369,6 +301,7 @@ impl fmt::Display for GameState {
 mod test {
     use super::*;
     use crate::card::Suit::{Black, FaceDown, Green, Red, Special};
+    use crate::deal::random_position;
     use googletest::matchers::*;
     use googletest::*;
     use std::collections::hash_map::DefaultHasher;
@@ -776,7 +709,7 @@ mod test {
         assert_that!(state.can_move_column_to_top_left(3), eq(true));
 
         // Moving a card causes it to disappear from the column
-        let moved_card = state.columns[0].last().unwrap().clone();
+        let moved_card = *state.columns[0].last().unwrap();
         state.move_column_to_top_left(0);
         assert_that!(state.columns[0].len(), eq(0));
         // The top left storage should be filled up
@@ -1037,7 +970,7 @@ mod test {
             eq(true)
         );
         let mut state_copy = state.clone();
-        let card_to_move = state_copy.columns[0].last().unwrap().clone();
+        let card_to_move = *state_copy.columns[0].last().unwrap();
         state_copy.move_column_to_other_column(MoveColumnParameters {
             from_column_index: 0,
             to_column_index: 7,
@@ -1083,4 +1016,84 @@ mod test {
             assert_that!(state_copy.columns[0].contains(card_to_move), eq(true));
         }
     }
+
+    #[test]
+    fn test_cells_are_compared() {
+        let a: GameState =
+            GameState::parse_unchecked("cells: R1\n1: G2\n2:\n3:\n4:\n5:\n6:\n7:\n8:\n").unwrap();
+        let b: GameState =
+            GameState::parse_unchecked("cells: G2\n1: R1\n2:\n3:\n4:\n5:\n6:\n7:\n8:\n").unwrap();
+
+        assert_that!(&a, not(eq(&b)));
+        assert_that!(calculate_hash(&a), not(eq(calculate_hash(&b))));
+    }
+
+    #[test]
+    fn test_columns_with_the_same_bottom_card() {
+        let a: GameState =
+            GameState::parse_unchecked("1: RD G2\n2: RD B5\n3:\n4:\n5:\n6:\n7:\n8:\n").unwrap();
+        let mut b = a.clone();
+        b.columns.swap(0, 1);
+        b.columns.swap(1, 6);
+
+        assert_that!(&a, eq(&b));
+        assert_that!(calculate_hash(&a), eq(calculate_hash(&b)));
+    }
+
+    #[test]
+    fn test_canonical() {
+        for seed in 0..30 {
+            let state = random_position(seed, 40);
+            let canonical = state.canonical();
+
+            // Every permutation of the columns and the cells gives the same
+            // canonical form
+            let mut permuted = state.clone();
+            permuted.columns.rotate_left(seed as usize % 8);
+            permuted.columns[..5].reverse();
+            permuted.top_left_storage.reverse();
+            let permuted_canonical = permuted.canonical();
+            assert_that!(&permuted_canonical.columns, eq(&canonical.columns));
+            assert_that!(
+                &permuted_canonical.top_left_storage,
+                eq(&canonical.top_left_storage)
+            );
+
+            let twice = canonical.canonical();
+            assert_that!(&twice.columns, eq(&canonical.columns));
+            assert_that!(&twice.top_left_storage, eq(&canonical.top_left_storage));
+            assert_that!(twice.top_right_storage, eq(canonical.top_right_storage));
+
+            assert_that!(&permuted, eq(&state));
+            assert_that!(&canonical, eq(&state));
+            assert_that!(calculate_hash(&permuted), eq(calculate_hash(&state)));
+            assert_that!(calculate_hash(&canonical), eq(calculate_hash(&state)));
+        }
+    }
+
+    #[test]
+    fn test_hash_eq_consistency() {
+        let states: Vec<GameState> = (0..10)
+            .flat_map(|seed| {
+                (0..40)
+                    .step_by(4)
+                    .map(move |moves| random_position(seed, moves))
+            })
+            .collect();
+
+        for a in &states {
+            for b in &states {
+                let canonical_a = a.canonical();
+                let canonical_b = b.canonical();
+                let same_canonical = canonical_a.columns == canonical_b.columns
+                    && canonical_a.top_left_storage == canonical_b.top_left_storage
+                    && canonical_a.top_right_storage == canonical_b.top_right_storage;
+
+                assert_that!(a == b, eq(same_canonical));
+                if a == b {
+                    assert_that!(calculate_hash(a), eq(calculate_hash(b)));
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,186 @@
+// Evaluation functions for the greedy search. The state with the highest
+// score is expanded first. The optimal search doesn't use these, it needs a
+// lower bound on the solution length instead.
+//
+// Any Fn(&GameState) -> i32 is also a heuristic, for quick experiments.
+
+use crate::card::*;
+use crate::game_state::GameState;
+
+pub trait Heuristic: Send + Sync {
+    fn score(&self, state: &GameState) -> i32;
+}
+
+impl<F> Heuristic for F
+where
+    F: Fn(&GameState) -> i32 + Send + Sync,
+{
+    fn score(&self, state: &GameState) -> i32 {
+        self(state)
+    }
+}
+
+fn collected_cards(state: &GameState) -> i32 {
+    state
+        .top_right_storage
+        .iter()
+        .map(|value| *value as i32)
+        .sum()
+}
+
+// Collected cards are good, and cards in a column with a dragon are bad
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultHeuristic;
+
+impl Heuristic for DefaultHeuristic {
+    fn score(&self, state: &GameState) -> i32 {
+        // comments are indicating what result we get if the code below the
+        // comment is removed
+        let mut score = 0;
+
+        // solution 0 length 173
+        // solution 1 length 120
+        // solution 2 length 275
+
+        // Collected cards are good
+        score += collected_cards(state);
+
+        // solution 0 length 64
+        // solution 1 length 76
+        // solution 2 length 87
+
+        // Cards hidden by dragons are bad
+        let blocked_card_count = state
+            .columns
+            .iter()
+            .filter(|column| column.iter().any(|card| card.is_dragon()))
+            .fold(0i32, |acc, column| {
+                acc + i32::try_from(column.len()).expect(
+                    "Column lengths are bounded by the number of cards in \
+                    play, which is always small",
+                )
+            });
+        score -= blocked_card_count;
+
+        score
+    }
+}
+
+// Collected cards are good, and cards on top of the next card each suit needs
+// for the foundation are bad
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NextCardDepth;
+
+impl Heuristic for NextCardDepth {
+    fn score(&self, state: &GameState) -> i32 {
+        let mut buried = 0;
+
+        for suit in [Suit::Red, Suit::Green, Suit::Black] {
+            let next = Card {
+                suit,
+                value: Some(state.top_right_storage[suit as usize] + 1),
+            };
+
+            // Not found when the suit is complete, or the card is in a cell,
+            // where it is free to move
+            for column in &state.columns {
+                if let Some(position) = column.iter().position(|card| *card == next) {
+                    buried += (column.len() - position - 1) as i32;
+                }
+            }
+        }
+
+        collected_cards(state) - buried
+    }
+}
+
+// Collected cards are good, and so is room to move cards around. An empty
+// column is worth more than a free cell, since it can take a whole stack.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FreeSpace;
+
+impl Heuristic for FreeSpace {
+    fn score(&self, state: &GameState) -> i32 {
+        let free_cells = 3 - state.top_left_storage.len() as i32;
+        let empty_columns = state
+            .columns
+            .iter()
+            .filter(|column| column.is_empty())
+            .count() as i32;
+
+        collected_cards(state) + free_cells + 2 * empty_columns
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::automatic::AutomaticMoves;
+    use crate::game::Game;
+    use crate::verify::verify_solution;
+    use googletest::assert_that;
+    use googletest::matchers::*;
+
+    fn state() -> GameState {
+        GameState::parse_unchecked(
+            "cells: RD XX\n\
+         foundation: F1 R2 G3 B1\n\
+         1: B9 R8\n\
+         2: G9 B6 R5 B4\n\
+         3:\n\
+         4: RD RD\n\
+         5: G4 B3 R3 B2\n\
+         6: GD\n\
+         7: RD B7\n\
+         8:\n",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_default_heuristic() {
+        // Seven collected cards, and five cards in columns with a dragon
+        assert_that!(DefaultHeuristic.score(&state()), eq(2));
+    }
+
+    #[test]
+    fn test_next_card_depth() {
+        // R3 has one card on top, G4 three and B2 none
+        assert_that!(NextCardDepth.score(&state()), eq(3));
+    }
+
+    #[test]
+    fn test_free_space() {
+        // One free cell and two empty columns
+        assert_that!(FreeSpace.score(&state()), eq(12));
+    }
+
+    #[test]
+    fn test_closure() {
+        let heuristic = |state: &GameState| state.top_left_storage.len() as i32;
+        assert_that!(heuristic.score(&state()), eq(2));
+    }
+
+    #[test]
+    fn test_builtins_solve() {
+        let heuristics: [Box<dyn Fn() -> Game>; 3] = [
+            Box::new(|| Game::new().with_heuristic(DefaultHeuristic)),
+            Box::new(|| Game::new().with_heuristic(NextCardDepth)),
+            Box::new(|| Game::new().with_heuristic(FreeSpace)),
+        ];
+
+        for game in heuristics {
+            for deal in [
+                include_str!("../deals/deal0.txt"),
+                include_str!("../deals/deal1.txt"),
+            ] {
+                let start: GameState = deal.parse().unwrap();
+                let solution = game().play(start.clone()).unwrap();
+                assert_that!(
+                    verify_solution(&start, &solution, AutomaticMoves::Emulated),
+                    ok(anything())
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+// The next move to make from a position, for players who want a nudge rather
+// than the whole solution. A player who follows the hints walks along the
+// solution found for the first hint, so the later hints are answered from it
+// without searching again.
+
+use crate::game::{Game, GameMove, SolveResult};
+use crate::game_state::GameState;
+use crate::limits::Limits;
+use crate::state_key::StateKey;
+use crate::verify::identical;
+use std::collections::HashSet;
+
+pub struct Hints {
+    new_game: Box<dyn Fn() -> Game + Send + Sync>,
+    // The positions of the last solution found, each with the move that led
+    // to it
+    solution: Vec<(GameState, GameMove)>,
+    // Positions proven to have no solution
+    lost: HashSet<StateKey>,
+}
+
+impl Default for Hints {
+    fn default() -> Self {
+        Self::new(Game::new)
+    }
+}
+
+impl Hints {
+    // new_game sets up the search used when a hint isn't known yet, its limits
+    // are replaced by the ones given to hint
+    pub fn new(new_game: impl Fn() -> Game + Send + Sync + 'static) -> Hints {
+        Hints {
+            new_game: Box::new(new_game),
+            solution: vec![],
+            lost: HashSet::new(),
+        }
+    }
+
+    // The first move of a solution from the position. None when the position
+    // is lost, see is_lost, when it is already solved, or when no solution was
+    // found within the limits.
+    //
+    // The position is what the player sees, so with emulated automatic moves
+    // the game has already made its moves.
+    pub fn hint(&mut self, state: &GameState, limits: Limits) -> Option<GameMove> {
+        if let Some(game_move) = self.known_hint(state) {
+            return Some(game_move);
+        }
+        if state.is_solved() || self.is_lost(state) {
+            return None;
+        }
+
+        let result = (self.new_game)().with_limits(limits).solve(state.clone());
+        if let SolveResult::ProvenUnsolvable = result {
+            self.lost.insert(StateKey::from(state));
+        }
+
+        self.solution = result.solution()?.steps;
+        self.known_hint(state)
+    }
+
+    // Whether an earlier hint proved the position has no solution
+    pub fn is_lost(&self, state: &GameState) -> bool {
+        self.lost.contains(&StateKey::from(state))
+    }
+
+    // The moves refer to columns and cells by their index, so the position
+    // has to match exactly, not just be equal
+    fn known_hint(&self, state: &GameState) -> Option<GameMove> {
+        let position = self
+            .solution
+            .iter()
+            .position(|(known, _)| identical(known, state))?;
+        self.solution
+            .get(position + 1)
+            .map(|(_, game_move)| game_move.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::deal::deal;
+    use googletest::assert_that;
+    use googletest::matchers::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_follow_hints() {
+        let searches = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&searches);
+        let mut hints = Hints::new(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Game::new()
+        });
+
+        let mut state: GameState = include_str!("../deals/deal0.txt").parse().unwrap();
+        state.apply_automatic_moves();
+        let mut moves = 0;
+        while let Some(game_move) = hints.hint(&state, Limits::default()) {
+            assert_that!(state.check_move(&game_move), ok(anything()));
+            state.apply_unchecked(&game_move);
+            state.apply_automatic_moves();
+            moves += 1;
+        }
+
+        assert_that!(state.is_solved(), eq(true));
+        assert_that!(moves, gt(0));
+        assert_that!(searches.load(Ordering::Relaxed), eq(1));
+    }
+
+    #[test]
+    fn test_search_again_after_another_move() {
+        let mut hints = Hints::default();
+        let mut state: GameState = include_str!("../deals/deal1.txt").parse().unwrap();
+        state.apply_automatic_moves();
+
+        let hint = hints.hint(&state, Limits::default()).unwrap();
+        let other = state
+            .legal_moves()
+            .into_iter()
+            .find(|game_move| *game_move != hint)
+            .unwrap();
+        state.apply_unchecked(&other);
+        state.apply_automatic_moves();
+
+        let game_move = hints.hint(&state, Limits::default()).unwrap();
+        assert_that!(state.check_move(&game_move), ok(anything()));
+    }
+
+    #[test]
+    fn test_lost() {
+        let mut hints = Hints::default();
+        let state = deal(9);
+        assert_that!(hints.is_lost(&state), eq(false));
+        assert_that!(hints.hint(&state, Limits::default()), none());
+        assert_that!(hints.is_lost(&state), eq(true));
+
+        // Running out of time doesn't prove anything
+        let mut hints = Hints::default();
+        let limits = Limits {
+            max_expanded_nodes: Some(1),
+            ..Limits::default()
+        };
+        assert_that!(hints.hint(&state, limits), none());
+        assert_that!(hints.is_lost(&state), eq(false));
+    }
+}
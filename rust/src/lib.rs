@@ -1,7 +1,47 @@
+mod automatic;
+mod batch;
 mod card;
+mod deal;
+mod export;
 mod game;
 mod game_state;
+mod heuristic;
+mod hint;
+mod limits;
+mod notation;
+mod progress;
+mod render;
+mod replay;
+mod rules;
+#[cfg(feature = "serde")]
+mod serialization;
+mod session;
+mod state_key;
+mod stats;
+mod undo;
+mod validation;
+mod verify;
 
+pub use automatic::*;
+pub use batch::*;
 pub use card::*;
+pub use deal::*;
+pub use export::*;
 pub use game::*;
 pub use game_state::*;
+pub use heuristic::*;
+pub use hint::*;
+pub use limits::*;
+pub use notation::*;
+pub use progress::*;
+pub use render::*;
+pub use replay::*;
+pub use rules::*;
+#[cfg(feature = "serde")]
+pub use serialization::SCHEMA_VERSION;
+pub use session::*;
+pub use state_key::*;
+pub use stats::*;
+pub use undo::*;
+pub use validation::*;
+pub use verify::*;
@@ -0,0 +1,90 @@
+// Bounds on how much work a search may do. Without them a hard or unsolvable
+// deal can run until the machine runs out of memory, since every state seen is
+// kept to avoid searching it twice.
+
+use std::fmt;
+use std::fmt::Formatter;
+use std::time::Instant;
+
+// Every limit is off by default. They are checked before each node is
+// expanded, so the search can go over a limit by one expansion.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub max_expanded_nodes: Option<usize>,
+    // Bounds the memory used, as each state seen is stored until the search
+    // finishes
+    pub max_stored_states: Option<usize>,
+    pub deadline: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    ExpandedNodes,
+    StoredStates,
+    Deadline,
+    // Through a CancellationToken
+    Cancelled,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Limit::ExpandedNodes => "node limit",
+            Limit::StoredStates => "state limit",
+            Limit::Deadline => "time limit",
+            Limit::Cancelled => "cancellation",
+        })
+    }
+}
+
+impl Limits {
+    // The first limit that has been reached, if any
+    pub fn reached(&self, expanded_nodes: usize, stored_states: usize) -> Option<Limit> {
+        if self
+            .max_expanded_nodes
+            .is_some_and(|limit| expanded_nodes >= limit)
+        {
+            Some(Limit::ExpandedNodes)
+        } else if self
+            .max_stored_states
+            .is_some_and(|limit| stored_states >= limit)
+        {
+            Some(Limit::StoredStates)
+        } else if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            Some(Limit::Deadline)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use googletest::assert_that;
+    use googletest::matchers::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_reached() {
+        assert_that!(Limits::default().reached(usize::MAX, usize::MAX), none());
+
+        let limits = Limits {
+            max_expanded_nodes: Some(10),
+            max_stored_states: Some(100),
+            deadline: Some(Instant::now() + Duration::from_secs(3600)),
+        };
+        assert_that!(limits.reached(9, 99), none());
+        assert_that!(limits.reached(10, 99), some(eq(Limit::ExpandedNodes)));
+        assert_that!(limits.reached(9, 100), some(eq(Limit::StoredStates)));
+
+        let limits = Limits {
+            deadline: Some(Instant::now()),
+            ..Limits::default()
+        };
+        assert_that!(limits.reached(0, 0), some(eq(Limit::Deadline)));
+    }
+}
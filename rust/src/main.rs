@@ -1,583 +1,1299 @@
-use crate::card::{Card, Suit};
-use crate::game::{Game, GameMove};
-use crate::game_state::GameState;
-
-mod card;
-mod game;
-mod game_state;
-
-fn main() {
-    let states = [
-        GameState {
-            top_left_storage: vec![],
-            top_right_storage: [0, 0, 0, 0],
-            columns: [
-                vec![
-                    Card {
-                        suit: Suit::Black,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(7),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(7),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(6),
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Red,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(9),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(8),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(7),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(4),
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(2),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(3),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(5),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(5),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(3),
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(1),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Special,
-                        value: Some(1),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(1),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(6),
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(4),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(8),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(2),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(6),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: None,
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(5),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(4),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(1),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(8),
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Green,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(3),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(2),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: None,
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(9),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(9),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: None,
-                    },
-                ],
-            ],
-        },
-        GameState {
-            top_left_storage: vec![],
-            top_right_storage: [0, 0, 0, 0],
-            columns: [
-                vec![
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(8),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(1),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(6),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: None,
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(1),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(4),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(3),
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(9),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(6),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(8),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(2),
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Green,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(3),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(7),
-                    },
-                    Card {
-                        suit: Suit::Special,
-                        value: Some(1),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(4),
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(5),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(6),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(3),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(8),
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Green,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(2),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(7),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(9),
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(5),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(5),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(4),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: None,
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(7),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(1),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(2),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(9),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: None,
-                    },
-                ],
-            ],
+use rust::{
+    random_position, AutomaticMoves, Batch, DefaultHeuristic, FreeSpace, Game, GameMove, GameState,
+    Limit, Limits, NextCardDepth, Replay, SearchMode, SearchStats, Session, Solution, SolveResult,
+    Style, Suit,
+};
+use serde_json::json;
+use std::fs;
+use std::io;
+use std::io::{BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const PLAY_HELP: &str = "\
+moves:
+  <from> <to> [count]  move cards, <from> is a column 1-8 or a cell c1-c3, and
+                       <to> is a column, c for a free cell or f for the
+                       foundation. count is the number of cards to move from
+                       column to column, defaults to 1
+  d <r|g|b>            collect the dragons of a suit
+
+commands:
+  u, undo    take back the last move
+  r, redo    make the last move taken back again
+  h, hint    suggest the next move
+  finish     play a solution to the end
+  show       print the position again
+  help       print this help
+  q, quit    stop playing
+";
+
+const VIEW_HELP: &str = "\
+commands:
+  n, next, Enter  show the next move
+  b, back         show the previous move
+  g, go <step>    jump to a step, 0 is the deal
+  p, play [seconds]
+                  step forward on a timer, one second per move by default.
+                  Enter pauses
+  show            print the step again
+  help            print this help
+  q, quit         stop viewing
+
+Lines marked with < lost cards in the move, the line marked with > gained
+them, and lines marked with * were changed by the moves the game made by
+itself. The moved cards are in brackets.
+";
+
+const USAGE: &str = "\
+usage: rust <command> [options]
+
+commands:
+  solve <file|->     solve a single deal, - reads it from stdin
+  batch <dir>        solve every .txt and .json deal in a directory
+  render <file|->    print a deal
+  validate <file|->  check that a deal can be read and is physically possible
+  generate <seed>    print the random deal for a seed
+  play <file>        play a deal interactively, type help once started for
+                     the commands
+  view <file>        step through a solution of a deal, type help once
+                     started for the commands
+  svg <file|->       draw a deal as an SVG image
+  html <file|->      write an HTML page that steps through or plays a
+                     solution of a deal
+
+Deals are read in the text notation, or as JSON when the input starts with {
+
+options:
+  --format text|json  output format, defaults to text
+  --output <path>     write the output to a file instead of stdout, except
+                      for play and view
+  --moves <count>     for generate, play this many random moves from the deal
+  --heuristic default|next-card|free-space
+                      the evaluation function for the default search
+  --optimal           find the solution with the fewest moves, this can be
+                      much slower
+  --node-limit <count>
+                      give up after expanding this many search nodes
+  --state-limit <count>
+                      give up after storing this many positions, which bounds
+                      the memory used
+  --timeout <seconds> give up on a deal after this long, for play this limits
+                      each hint
+                      When a limit is reached, the best solution found so far
+                      is returned, if there is one.
+  --progress          report the search progress on stderr every second
+  --stats             include statistics about the search in the output
+  --style ascii|ansi|emoji
+                      for render in text format, how to draw the cards,
+                      defaults to ascii. ansi adds colours for terminals.
+  --solution <path>   for view and html, the solution to show, as written by
+                      solve in either format. The deal is solved when not
+                      given.
+  --threads <count>   for solve, search on this many threads, which gives
+                      the same result every time for the same count
+                      for batch, solve this many deals at once, defaults to
+                      the number of cores
+
+exit codes:
+  0   solved, or the deal is valid
+  1   the deal has no solution
+  2   the input could not be read, or is not a possible position
+  3   a search limit was reached before finding a solution
+  64  the command line arguments are wrong
+";
+
+// Ordered by severity, batch runs exit with the most severe outcome of all the
+// deals
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Outcome {
+    Solved,
+    Unsolvable,
+    LimitReached,
+    InvalidInput,
+    Usage,
+}
+
+impl From<Outcome> for ExitCode {
+    fn from(outcome: Outcome) -> Self {
+        ExitCode::from(match outcome {
+            Outcome::Solved => 0,
+            Outcome::Unsolvable => 1,
+            Outcome::LimitReached => 3,
+            Outcome::InvalidInput => 2,
+            Outcome::Usage => 64,
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Clone)]
+enum Command {
+    Solve(String),
+    Batch(PathBuf),
+    Render(String),
+    Validate(String),
+    Generate(u64),
+    Play(String),
+    View(String),
+    Svg(String),
+    Html(String),
+}
+
+#[derive(Clone, Copy)]
+enum HeuristicName {
+    Default,
+    NextCard,
+    FreeSpace,
+}
+
+impl HeuristicName {
+    fn apply(self, game: Game) -> Game {
+        match self {
+            HeuristicName::Default => game.with_heuristic(DefaultHeuristic),
+            HeuristicName::NextCard => game.with_heuristic(NextCardDepth),
+            HeuristicName::FreeSpace => game.with_heuristic(FreeSpace),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Options {
+    command: Command,
+    format: Format,
+    output: Option<PathBuf>,
+    moves: usize,
+    heuristic: HeuristicName,
+    search_mode: SearchMode,
+    node_limit: Option<usize>,
+    state_limit: Option<usize>,
+    timeout: Option<Duration>,
+    progress: bool,
+    stats: bool,
+    threads: Option<usize>,
+    solution: Option<PathBuf>,
+    style: Style,
+}
+
+// Returns None when help was requested
+fn parse_arguments(mut arguments: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut command_name = None;
+    let mut input = None;
+    let mut format = Format::Text;
+    let mut output = None;
+    let mut moves = 0;
+    let mut heuristic = HeuristicName::Default;
+    let mut search_mode = SearchMode::Greedy;
+    let mut node_limit = None;
+    let mut state_limit = None;
+    let mut timeout = None;
+    let mut progress = false;
+    let mut stats = false;
+    let mut threads = None;
+    let mut solution = None;
+    let mut style = Style::Ascii;
+
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--format" => {
+                format = match arguments.next().as_deref() {
+                    Some("text") => Format::Text,
+                    Some("json") => Format::Json,
+                    _ => return Err(String::from("--format must be followed by text or json")),
+                }
+            }
+            "--output" => match arguments.next() {
+                None => return Err(String::from("--output must be followed by a path")),
+                Some(path) => output = Some(PathBuf::from(path)),
+            },
+            "--moves" => match arguments.next().map(|count| count.parse()) {
+                Some(Ok(count)) => moves = count,
+                _ => return Err(String::from("--moves must be followed by a move count")),
+            },
+            "--heuristic" => {
+                heuristic = match arguments.next().as_deref() {
+                    Some("default") => HeuristicName::Default,
+                    Some("next-card") => HeuristicName::NextCard,
+                    Some("free-space") => HeuristicName::FreeSpace,
+                    _ => {
+                        return Err(String::from(
+                            "--heuristic must be followed by default, next-card or free-space",
+                        ))
+                    }
+                }
+            }
+            "--optimal" => search_mode = SearchMode::Optimal,
+            "--node-limit" => match arguments.next().map(|count| count.parse()) {
+                Some(Ok(count)) => node_limit = Some(count),
+                _ => {
+                    return Err(String::from(
+                        "--node-limit must be followed by a node count",
+                    ))
+                }
+            },
+            "--state-limit" => match arguments.next().map(|count| count.parse()) {
+                Some(Ok(count)) => state_limit = Some(count),
+                _ => {
+                    return Err(String::from(
+                        "--state-limit must be followed by a state count",
+                    ))
+                }
+            },
+            "--timeout" => match arguments.next().map(|seconds| seconds.parse::<f64>()) {
+                Some(Ok(seconds)) if seconds >= 0.0 && seconds.is_finite() => {
+                    timeout = Some(Duration::from_secs_f64(seconds))
+                }
+                _ => {
+                    return Err(String::from(
+                        "--timeout must be followed by a number of seconds",
+                    ))
+                }
+            },
+            "--progress" => progress = true,
+            "--stats" => stats = true,
+            "--threads" => match arguments.next().map(|count| count.parse()) {
+                Some(Ok(count)) if count > 0 => threads = Some(count),
+                _ => return Err(String::from("--threads must be followed by a thread count")),
+            },
+            "--style" => {
+                style = match arguments.next().as_deref() {
+                    Some("ascii") => Style::Ascii,
+                    Some("ansi") => Style::Ansi,
+                    Some("emoji") => Style::Emoji,
+                    _ => {
+                        return Err(String::from(
+                            "--style must be followed by ascii, ansi or emoji",
+                        ))
+                    }
+                }
+            }
+            "--solution" => match arguments.next() {
+                None => return Err(String::from("--solution must be followed by a path")),
+                Some(path) => solution = Some(PathBuf::from(path)),
+            },
+            "-h" | "--help" => return Ok(None),
+            _ if command_name.is_none() => command_name = Some(argument),
+            _ if input.is_none() => input = Some(argument),
+            _ => return Err(format!("unexpected argument `{}`", argument)),
+        }
+    }
+
+    let command_name = command_name.ok_or_else(|| String::from("missing command"))?;
+    let input = input.ok_or_else(|| format!("{} needs an input", command_name))?;
+
+    let command = match command_name.as_str() {
+        "solve" => Command::Solve(input),
+        "batch" => Command::Batch(PathBuf::from(input)),
+        "render" => Command::Render(input),
+        "validate" => Command::Validate(input),
+        "generate" => match input.parse() {
+            Err(_) => return Err(format!("invalid seed `{}`", input)),
+            Ok(seed) => Command::Generate(seed),
         },
-        GameState {
-            top_left_storage: vec![],
-            top_right_storage: [0, 0, 0, 0],
-            columns: [
-                vec![
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(1),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(4),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(5),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(7),
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(4),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(4),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(6),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(3),
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Red,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(7),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Special,
-                        value: Some(1),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(9),
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Green,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(1),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(3),
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(3),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(2),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(5),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(1),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(7),
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Black,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(8),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(2),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(2),
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(8),
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Red,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Green,
-                        value: Some(9),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(9),
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(5),
-                    },
-                ],
-                vec![
-                    Card {
-                        suit: Suit::Black,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: None,
-                    },
-                    Card {
-                        suit: Suit::Red,
-                        value: Some(6),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(8),
-                    },
-                    Card {
-                        suit: Suit::Black,
-                        value: Some(6),
-                    },
-                ],
-            ],
+        // The moves are read from stdin
+        "play" if input == "-" => return Err(String::from("play needs a deal file")),
+        "play" => Command::Play(input),
+        "view" if input == "-" => return Err(String::from("view needs a deal file")),
+        "view" => Command::View(input),
+        "svg" => Command::Svg(input),
+        "html" => Command::Html(input),
+        _ => return Err(format!("unknown command `{}`", command_name)),
+    };
+
+    // They print to the terminal as they go, there is no output to write
+    if output.is_some() && matches!(command, Command::Play(_) | Command::View(_)) {
+        return Err(format!("--output can't be used with {}", command_name));
+    }
+
+    Ok(Some(Options {
+        command,
+        format,
+        output,
+        moves,
+        heuristic,
+        search_mode,
+        node_limit,
+        state_limit,
+        timeout,
+        progress,
+        stats,
+        threads,
+        solution,
+        style,
+    }))
+}
+
+fn parse_deal(text: &str) -> Result<GameState, String> {
+    if text.trim_start().starts_with('{') {
+        serde_json::from_str(text).map_err(|error| error.to_string())
+    } else {
+        // Validated by read_deal, which reports every violation separately
+        GameState::parse_unchecked(text).map_err(|error| error.to_string())
+    }
+}
+
+// Reads, parses and validates a deal, returning all the errors found
+fn read_deal(input: &str) -> Result<GameState, Vec<String>> {
+    let mut text = String::new();
+    let result = if input == "-" {
+        io::stdin().read_to_string(&mut text).map(|_| ())
+    } else {
+        fs::read_to_string(input).map(|contents| text = contents)
+    };
+
+    result.map_err(|error| vec![format!("{}: {}", input, error)])?;
+    let state = parse_deal(&text).map_err(|error| vec![format!("{}: {}", input, error)])?;
+
+    state.validate().map_err(|violations| {
+        violations
+            .iter()
+            .map(|violation| format!("{}: {}", input, violation))
+            .collect::<Vec<String>>()
+    })?;
+
+    Ok(state)
+}
+
+// The time limit applies to each search separately
+fn limits(options: &Options) -> Limits {
+    Limits {
+        max_expanded_nodes: options.node_limit,
+        max_stored_states: options.state_limit,
+        deadline: options.timeout.map(|timeout| Instant::now() + timeout),
+    }
+}
+
+fn new_game(options: &Options) -> Game {
+    let game = options
+        .heuristic
+        .apply(Game::new())
+        .with_search_mode(options.search_mode)
+        .with_limits(limits(options));
+
+    if !options.progress {
+        return game;
+    }
+    game.with_progress(Duration::from_secs(1), |progress| {
+        eprintln!(
+            "{:.0}s: {} nodes expanded, {} open, {} stored, best priority {}",
+            progress.elapsed.as_secs_f64(),
+            progress.expanded_nodes,
+            progress.open_states,
+            progress.stored_states,
+            progress.best_priority
+        )
+    })
+}
+
+fn limit_name(limit: Limit) -> &'static str {
+    match limit {
+        Limit::ExpandedNodes => "expanded_nodes",
+        Limit::StoredStates => "stored_states",
+        Limit::Deadline => "deadline",
+        Limit::Cancelled => "cancelled",
+    }
+}
+
+// A solution is still a success when the search stopped early, there just
+// might be a shorter one
+fn outcome(result: &SolveResult) -> Outcome {
+    match result {
+        SolveResult::Solved(_) | SolveResult::LimitReached { best: Some(_), .. } => Outcome::Solved,
+        SolveResult::ProvenUnsolvable => Outcome::Unsolvable,
+        SolveResult::LimitReached { best: None, .. } => Outcome::LimitReached,
+        SolveResult::InvalidStart(_) => Outcome::InvalidInput,
+    }
+}
+
+fn format_solution(solution: &Solution, search_mode: SearchMode, limit: Option<Limit>) -> String {
+    let mut result = format!("# solved in {} moves", solution.move_count());
+    match (search_mode, solution.proven_optimal) {
+        (SearchMode::Greedy, _) => {}
+        (SearchMode::Optimal, true) => result += ", optimal",
+        (SearchMode::Optimal, false) => result += ", not proven optimal",
+    }
+    if let Some(limit) = limit {
+        result += &format!(", stopped at the {}", limit);
+    }
+    result += "\n";
+
+    for (i, (_, game_move)) in solution.steps.iter().enumerate().skip(1) {
+        result += &format!("{}. {}\n", i, game_move);
+    }
+    result
+}
+
+fn stats_json(stats: &SearchStats) -> serde_json::Value {
+    json!({
+        "expanded_nodes": stats.expanded_nodes,
+        "generated_nodes": stats.generated_nodes,
+        "duplicates": stats.duplicates,
+        "peak_open_states": stats.peak_open_states,
+        "automatic_moves": stats.automatic_moves,
+        "max_depth": stats.max_depth,
+        "move_generation_seconds": stats.move_generation_time.as_secs_f64(),
+        "hashing_seconds": stats.hashing_time.as_secs_f64(),
+        "total_seconds": stats.total_time.as_secs_f64(),
+    })
+}
+
+fn solve(input: &str, options: &Options) -> (Outcome, String) {
+    let state = match read_deal(input) {
+        Err(errors) => return (Outcome::InvalidInput, errors.join("\n")),
+        Ok(state) => state,
+    };
+
+    let mut game = new_game(options);
+    if let Some(threads) = options.threads {
+        game = game.with_threads(threads);
+    }
+    let (result, stats) = game.solve_with_stats(state);
+    let outcome = outcome(&result);
+    if let SolveResult::InvalidStart(violations) = &result {
+        let errors: Vec<String> = violations
+            .iter()
+            .map(|violation| format!("{}: {}", input, violation))
+            .collect();
+        return (outcome, errors.join("\n"));
+    }
+
+    let mut text = match (options.format, result) {
+        (Format::Text, SolveResult::Solved(solution)) => {
+            format_solution(&solution, options.search_mode, None)
+        }
+        (
+            Format::Text,
+            SolveResult::LimitReached {
+                limit,
+                best: Some(solution),
+            },
+        ) => format_solution(&solution, options.search_mode, Some(limit)),
+        (Format::Text, SolveResult::ProvenUnsolvable) => String::from("# no solution\n"),
+        (Format::Text, SolveResult::LimitReached { limit, best: None }) => {
+            format!("# no solution found, stopped at the {}\n", limit)
+        }
+        (Format::Json, SolveResult::ProvenUnsolvable) => {
+            json!({ "status": "unsolvable" }).to_string()
+        }
+        (Format::Json, SolveResult::LimitReached { limit, best: None }) => json!({
+            "status": "limit_reached",
+            "limit": limit_name(limit),
+        })
+        .to_string(),
+        (_, SolveResult::InvalidStart(_)) => unreachable!("Reported above"),
+        (Format::Json, result) => {
+            let limit = match &result {
+                SolveResult::LimitReached { limit, .. } => Some(limit_name(*limit)),
+                _ => None,
+            };
+            let solution = result.solution().unwrap();
+            json!({
+                "status": "solved",
+                "moves": solution.move_count(),
+                "optimal": solution.proven_optimal,
+                "limit": limit,
+                "solution": solution.steps,
+            })
+            .to_string()
+        }
+    };
+
+    if options.stats {
+        match options.format {
+            Format::Text => {
+                for line in stats.to_string().lines() {
+                    text += &format!("# {}\n", line);
+                }
+            }
+            Format::Json => {
+                let mut value: serde_json::Value = serde_json::from_str(&text).unwrap();
+                value["stats"] = stats_json(&stats);
+                text = value.to_string();
+            }
+        }
+    }
+
+    (outcome, text)
+}
+
+fn batch_deals(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        if path.is_file() && matches!(extension, Some("txt" | "json")) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+fn batch(directory: &Path, options: &Options) -> (Outcome, String) {
+    let paths = match batch_deals(directory) {
+        Err(error) => {
+            let error = format!("{}: {}", directory.display(), error);
+            return match options.format {
+                Format::Text => (Outcome::InvalidInput, error),
+                Format::Json => (Outcome::InvalidInput, errors_json(&[error])),
+            };
+        }
+        Ok(paths) => paths,
+    };
+
+    // Invalid deals are reported in the same place as the others, so read them
+    // all before solving the valid ones
+    let deals: Vec<(String, Result<GameState, Vec<String>>)> = paths
+        .iter()
+        .map(|path| {
+            let name = path.display().to_string();
+            let deal = read_deal(&name);
+            (name, deal)
+        })
+        .collect();
+    let states: Vec<GameState> = deals
+        .iter()
+        .filter_map(|(_, deal)| deal.as_ref().ok().cloned())
+        .collect();
+
+    let game_options = Arc::new(options.clone());
+    let mut batch = Batch::new(move || new_game(&game_options));
+    if let Some(threads) = options.threads {
+        batch = batch.with_threads(threads);
+    }
+    let mut solved = batch.solve(states);
+
+    let mut worst = Outcome::Solved;
+    let mut lines = String::new();
+    let mut results = vec![];
+
+    for (name, deal) in deals {
+        let (outcome, limit, moves, stats, errors) = match deal {
+            Err(errors) => (Outcome::InvalidInput, None, None, None, errors),
+            Ok(_) => {
+                let batch_result = solved.next().expect("Every valid deal has a result");
+                let (result, stats) = (batch_result.result, batch_result.stats);
+                let outcome = outcome(&result);
+                let limit = match &result {
+                    SolveResult::LimitReached { limit, .. } => Some(*limit),
+                    _ => None,
+                };
+                let moves = result.solution().map(|solution| solution.move_count());
+                (outcome, limit, moves, Some(stats), vec![])
+            }
+        };
+        worst = worst.max(outcome);
+
+        match (outcome, moves, limit) {
+            (Outcome::Solved, Some(moves), None) => {
+                lines += &format!("{}: solved in {} moves\n", name, moves)
+            }
+            (Outcome::Solved, Some(moves), Some(limit)) => {
+                lines += &format!(
+                    "{}: solved in {} moves, stopped at the {}\n",
+                    name, moves, limit
+                )
+            }
+            (Outcome::Unsolvable, _, _) => lines += &format!("{}: no solution\n", name),
+            (Outcome::LimitReached, _, Some(limit)) => {
+                lines += &format!("{}: no solution found, stopped at the {}\n", name, limit)
+            }
+            _ => {
+                for error in &errors {
+                    lines += &format!("{}\n", error);
+                }
+            }
+        }
+
+        if let (true, Some(stats)) = (options.stats, &stats) {
+            lines += &format!(
+                "# {} nodes expanded, {} generated, {:.3}s\n",
+                stats.expanded_nodes,
+                stats.generated_nodes,
+                stats.total_time.as_secs_f64()
+            );
+        }
+
+        let mut result = json!({
+            "file": name,
+            "status": match outcome {
+                Outcome::Solved => "solved",
+                Outcome::Unsolvable => "unsolvable",
+                Outcome::LimitReached => "limit_reached",
+                _ => "invalid",
+            },
+            "limit": limit.map(limit_name),
+            "moves": moves,
+            "errors": errors,
+        });
+        if let (true, Some(stats)) = (options.stats, &stats) {
+            result["stats"] = stats_json(stats);
+        }
+        results.push(result);
+    }
+
+    match options.format {
+        Format::Text => (worst, lines),
+        Format::Json => (worst, json!(results).to_string()),
+    }
+}
+
+fn render(input: &str, format: Format, style: Style) -> (Outcome, String) {
+    match (read_deal(input), format) {
+        (Err(errors), _) => (Outcome::InvalidInput, errors.join("\n")),
+        (Ok(state), Format::Text) => (Outcome::Solved, state.render(style).to_string()),
+        (Ok(state), Format::Json) => (Outcome::Solved, json!(state).to_string()),
+    }
+}
+
+fn validate(input: &str, format: Format) -> (Outcome, String) {
+    let result = read_deal(input);
+    match (result, format) {
+        (Err(errors), Format::Text) => (Outcome::InvalidInput, errors.join("\n")),
+        (Ok(_), Format::Text) => (Outcome::Solved, format!("{}: valid\n", input)),
+        (Err(errors), Format::Json) => (
+            Outcome::InvalidInput,
+            json!({ "valid": false, "errors": errors }).to_string(),
+        ),
+        (Ok(_), Format::Json) => (
+            Outcome::Solved,
+            json!({ "valid": true, "errors": [] }).to_string(),
+        ),
+    }
+}
+
+fn generate(seed: u64, moves: usize, format: Format) -> (Outcome, String) {
+    let state = random_position(seed, moves);
+    let text = match format {
+        Format::Text => format!(
+            "# seed {} after {} moves\n{}",
+            seed,
+            moves,
+            state.notation()
+        ),
+        Format::Json => json!(state).to_string(),
+    };
+    (Outcome::Solved, text)
+}
+
+enum PlayCommand {
+    Move(GameMove),
+    Undo,
+    Redo,
+    Hint,
+    Finish,
+    Show,
+    Help,
+    Quit,
+}
+
+enum Place {
+    Column(usize),
+    // Moving to a cell always takes the first free one
+    Cell(Option<usize>),
+    Foundation,
+}
+
+fn parse_place(word: &str) -> Option<Place> {
+    let number = |text: &str, count: usize| match text.parse::<usize>() {
+        Ok(number) if (1..=count).contains(&number) => Some(number - 1),
+        _ => None,
+    };
+
+    match word {
+        "f" => Some(Place::Foundation),
+        "c" => Some(Place::Cell(None)),
+        _ => match word.strip_prefix('c') {
+            Some(cell) => number(cell, 3).map(|cell| Place::Cell(Some(cell))),
+            None => number(word, 8).map(Place::Column),
         },
-    ];
+    }
+}
+
+fn parse_play_command(line: &str) -> Result<PlayCommand, String> {
+    let words: Vec<String> = line.split_whitespace().map(str::to_lowercase).collect();
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+
+    let command = match words.as_slice() {
+        ["u" | "undo"] => PlayCommand::Undo,
+        ["r" | "redo"] => PlayCommand::Redo,
+        ["h" | "hint"] => PlayCommand::Hint,
+        ["finish"] => PlayCommand::Finish,
+        ["show"] => PlayCommand::Show,
+        ["help"] => PlayCommand::Help,
+        ["q" | "quit"] => PlayCommand::Quit,
+        ["d", suit] => PlayCommand::Move(GameMove::CollectDragons {
+            suit: match *suit {
+                "r" => Suit::Red,
+                "g" => Suit::Green,
+                "b" => Suit::Black,
+                _ => return Err(format!("`{}` is not a dragon suit, use r, g or b", suit)),
+            },
+        }),
+        [from, to, rest @ ..] if rest.len() <= 1 => {
+            let place = |word: &str| {
+                parse_place(word).ok_or_else(|| format!("`{}` is not a column or a cell", word))
+            };
+            let stack_size = match rest {
+                [] => None,
+                [count] => match count.parse() {
+                    Ok(count) => Some(count),
+                    Err(_) => return Err(format!("`{}` is not a card count", count)),
+                },
+                _ => unreachable!(),
+            };
+
+            let game_move = match (place(from)?, place(to)?, stack_size) {
+                (Place::Column(from), Place::Column(to), stack_size) => {
+                    GameMove::ColumnToOtherColumn {
+                        from_column_index: from,
+                        to_column_index: to,
+                        stack_size: stack_size.unwrap_or(1),
+                    }
+                }
+                (_, _, Some(_)) => {
+                    return Err(String::from(
+                        "a card count is only used to move from column to column",
+                    ))
+                }
+                (Place::Column(column), Place::Cell(_), None) => {
+                    GameMove::ToTopLeftStorage { column }
+                }
+                (Place::Column(column), Place::Foundation, None) => {
+                    GameMove::ColumnToTopRightStorage { column }
+                }
+                (Place::Cell(Some(top_left_index)), Place::Column(column_index), None) => {
+                    GameMove::TopLeftToColumn {
+                        top_left_index,
+                        column_index,
+                    }
+                }
+                (Place::Cell(Some(top_left_index)), Place::Foundation, None) => {
+                    GameMove::TopLeftToTopRightStorage { top_left_index }
+                }
+                _ => return Err(format!("can't move from {} to {}", from, to)),
+            };
+            PlayCommand::Move(game_move)
+        }
+        _ => {
+            return Err(format!(
+                "unknown command `{}`, type help for a list",
+                line.trim()
+            ))
+        }
+    };
+
+    Ok(command)
+}
+
+fn print_position(session: &Session) {
+    print!("\n{}", session.state().notation());
+    if session.state().is_solved() {
+        println!("solved in {} moves", session.move_count());
+    }
+}
+
+fn print_automatic_moves(moves: &[GameMove]) {
+    if !moves.is_empty() {
+        let moves: Vec<String> = moves
+            .iter()
+            .map(|game_move| game_move.to_string())
+            .collect();
+        println!("the game moved: {}", moves.join(", "));
+    }
+}
+
+// Reads commands from stdin until it ends or the player quits. Everything is
+// printed straight away, so nothing is left for the output.
+fn play(input: &str, options: &Options) -> (Outcome, String) {
+    let state = match read_deal(input) {
+        Err(errors) => return (Outcome::InvalidInput, errors.join("\n")),
+        Ok(state) => state,
+    };
+
+    let mut session = Session::new(state, AutomaticMoves::Emulated);
+    println!("type help for the commands");
+    print_position(&session);
+
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+        let Some(Ok(line)) = lines.next() else {
+            println!();
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let command = match parse_play_command(&line) {
+            Err(error) => {
+                println!("{}", error);
+                continue;
+            }
+            Ok(command) => command,
+        };
+
+        match command {
+            PlayCommand::Move(game_move) => match session.play(&game_move) {
+                Err(error) => println!("{}", error),
+                Ok(automatic) => {
+                    print_automatic_moves(&automatic);
+                    print_position(&session);
+                }
+            },
+            PlayCommand::Undo => match session.undo() {
+                None => println!("nothing to undo"),
+                Some(game_move) => {
+                    println!("took back {}", game_move);
+                    print_position(&session);
+                }
+            },
+            PlayCommand::Redo => match session.redo() {
+                None => println!("nothing to redo"),
+                Some((game_move, automatic)) => {
+                    println!("made {} again", game_move);
+                    print_automatic_moves(&automatic);
+                    print_position(&session);
+                }
+            },
+            PlayCommand::Hint => match session.hint(limits(options)) {
+                Some(game_move) => println!("hint: {}", game_move),
+                None if session.state().is_solved() => println!("already solved"),
+                None if session.is_lost() => println!("there is no solution from here"),
+                None => println!("no solution found within the limits"),
+            },
+            PlayCommand::Finish => match session.finish(limits(options)) {
+                Some(moves) => {
+                    for game_move in moves {
+                        println!("{}", game_move);
+                    }
+                    print_position(&session);
+                }
+                None if session.is_lost() => println!("there is no solution from here"),
+                None => println!("no solution found within the limits"),
+            },
+            PlayCommand::Show => print_position(&session),
+            PlayCommand::Help => print!("{}", PLAY_HELP),
+            PlayCommand::Quit => break,
+        }
+    }
+
+    (Outcome::Solved, String::new())
+}
+
+// Reads the moves of a solution written by solve, in either format
+fn read_solution(path: &Path) -> Result<Vec<GameMove>, String> {
+    let name = path.display();
+    let text = fs::read_to_string(path).map_err(|error| format!("{}: {}", name, error))?;
+
+    if text.trim_start().starts_with('{') {
+        let value: serde_json::Value =
+            serde_json::from_str(&text).map_err(|error| format!("{}: {}", name, error))?;
+        if value["solution"].is_null() {
+            return Err(format!("{}: has no solution", name));
+        }
+        let steps: Vec<(GameState, GameMove)> = serde_json::from_value(value["solution"].clone())
+            .map_err(|error| format!("{}: {}", name, error))?;
+        return Ok(steps.into_iter().map(|(_, game_move)| game_move).collect());
+    }
+
+    let mut moves = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = match line.find('#') {
+            None => line,
+            Some(comment_start) => &line[..comment_start],
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        // Each move is numbered, as in `3. column 2 to cell`
+        let (prefix, game_move) = match line.split_once(". ") {
+            Some((number, game_move)) if number.trim().parse::<usize>().is_ok() => {
+                (number.chars().count() + 2, game_move)
+            }
+            _ => (0, line),
+        };
+        let game_move = game_move.parse().map_err(|mut error: rust::ParseError| {
+            error.line = i + 1;
+            error.column += prefix;
+            format!("{}: {}", name, error)
+        })?;
+        moves.push(game_move);
+    }
+    Ok(moves)
+}
+
+// The notation of the position after a step, with the places the step changed
+// marked
+fn format_step(replay: &Replay, step: usize) -> String {
+    let mut result = match step {
+        0 => format!("deal, {} moves to go\n", replay.steps.len()),
+        _ => {
+            let replay_step = &replay.steps[step - 1];
+            let mut header = format!(
+                "step {} of {}: {}\n",
+                step,
+                replay.steps.len(),
+                replay_step.movement.game_move
+            );
+            if !replay_step.automatic.is_empty() {
+                let moves: Vec<String> = replay_step
+                    .automatic
+                    .iter()
+                    .map(|movement| movement.game_move.to_string())
+                    .collect();
+                header += &format!("the game then moved: {}\n", moves.join(", "));
+            }
+            header
+        }
+    };
+
+    // One entry per line of the notation, the cells, the foundation and then
+    // the columns
+    let mut marks = [' '; 10];
+    let mut moved: [Option<(usize, usize)>; 10] = Default::default();
+    let line = |place: &rust::Place| match place {
+        rust::Place::Cell(_) => 0,
+        rust::Place::Foundation(_) => 1,
+        rust::Place::Column(column) => 2 + column,
+    };
+
+    let state = replay.state(step);
+    if step > 0 {
+        let replay_step = &replay.steps[step - 1];
+        for movement in &replay_step.automatic {
+            for place in movement.from.iter().chain([&movement.to]) {
+                marks[line(place)] = '*';
+            }
+        }
+
+        let movement = &replay_step.movement;
+        for place in &movement.from {
+            marks[line(place)] = '<';
+        }
+        marks[line(&movement.to)] = '>';
+
+        if let Some((place, count)) = replay_step.landed() {
+            moved[line(&place)] = Some(match place {
+                rust::Place::Column(column) => {
+                    let len = state.columns[column].len();
+                    (len - count, len)
+                }
+                rust::Place::Cell(cell) => (cell, cell + 1),
+                rust::Place::Foundation(suit) => (suit as usize, suit as usize + 1),
+            });
+        }
+    }
+
+    for (i, text) in state.notation().to_string().lines().enumerate() {
+        let (label, cards) = text.split_once(':').unwrap();
+        let mut tokens: Vec<String> = cards.split_whitespace().map(String::from).collect();
+        if let Some((first, last)) = moved[i] {
+            tokens[first].insert(0, '[');
+            tokens[last - 1].push(']');
+        }
+        result += &format!("{} {}: {}\n", marks[i], label, tokens.join(" ")).replace(": \n", ":\n");
+    }
 
-    for state in &states {
-        println!("{}", state);
+    if step == replay.steps.len() && state.is_solved() {
+        result += "solved\n";
     }
+    result
+}
+
+enum ViewCommand {
+    Next,
+    Back,
+    Go(usize),
+    Play(Duration),
+    Show,
+    Help,
+    Quit,
+}
 
-    for (i, state) in states.iter().enumerate() {
-        let mut game = Game::new();
-        let solution = game.play(state.clone());
+fn parse_view_command(line: &str) -> Result<ViewCommand, String> {
+    let words: Vec<String> = line.split_whitespace().map(str::to_lowercase).collect();
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+
+    let command = match words.as_slice() {
+        [] | ["n" | "next"] => ViewCommand::Next,
+        ["b" | "back"] => ViewCommand::Back,
+        ["g" | "go", step] => match step.parse() {
+            Ok(step) => ViewCommand::Go(step),
+            Err(_) => return Err(format!("`{}` is not a step number", step)),
+        },
+        ["p" | "play"] => ViewCommand::Play(Duration::from_secs(1)),
+        ["p" | "play", seconds] => match seconds.parse::<f64>() {
+            Ok(seconds) if seconds > 0.0 && seconds.is_finite() => {
+                ViewCommand::Play(Duration::from_secs_f64(seconds))
+            }
+            _ => return Err(format!("`{}` is not a number of seconds", seconds)),
+        },
+        ["show"] => ViewCommand::Show,
+        ["help"] => ViewCommand::Help,
+        ["q" | "quit"] => ViewCommand::Quit,
+        _ => {
+            return Err(format!(
+                "unknown command `{}`, type help for a list",
+                line.trim()
+            ))
+        }
+    };
+
+    Ok(command)
+}
+
+// Reads stdin on another thread, so the viewer can wait for a line with a
+// timeout while playing
+fn stdin_lines() -> Receiver<String> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
 
-        match solution {
+// Reads a deal, and the solution given with --solution or else solves it
+fn read_replay(input: &str, options: &Options) -> Result<Replay, (Outcome, String)> {
+    let state = read_deal(input).map_err(|errors| (Outcome::InvalidInput, errors.join("\n")))?;
+
+    let moves = match &options.solution {
+        Some(path) => read_solution(path).map_err(|error| (Outcome::InvalidInput, error))?,
+        None => {
+            let mut game = new_game(options);
+            if let Some(threads) = options.threads {
+                game = game.with_threads(threads);
+            }
+            let result = game.solve(state.clone());
+            let outcome = outcome(&result);
+            match result {
+                SolveResult::ProvenUnsolvable => {
+                    return Err((outcome, String::from("# no solution\n")))
+                }
+                SolveResult::LimitReached { limit, best: None } => {
+                    return Err((
+                        outcome,
+                        format!("# no solution found, stopped at the {}\n", limit),
+                    ))
+                }
+                SolveResult::InvalidStart(_) => unreachable!("read_deal validates the deal"),
+                result => {
+                    let solution = result.solution().unwrap();
+                    solution
+                        .steps
+                        .into_iter()
+                        .map(|(_, game_move)| game_move)
+                        .collect()
+                }
+            }
+        }
+    };
+
+    Replay::new(&state, &moves, AutomaticMoves::Emulated)
+        .map_err(|error| (Outcome::InvalidInput, format!("{}: {}", input, error)))
+}
+
+// Reads commands from stdin until it ends or the viewer quits. Everything is
+// printed straight away, so nothing is left for the output.
+fn view(input: &str, options: &Options) -> (Outcome, String) {
+    let replay = match read_replay(input, options) {
+        Err(error) => return error,
+        Ok(replay) => replay,
+    };
+
+    let last = replay.steps.len();
+    let mut step = 0;
+    // Some while playing
+    let mut interval = None;
+    println!("type help for the commands\n");
+    print!("{}", format_step(&replay, step));
+
+    let lines = stdin_lines();
+    loop {
+        let line = match interval {
+            Some(timeout) => match lines.recv_timeout(timeout) {
+                Err(RecvTimeoutError::Timeout) => {
+                    step += 1;
+                    print!("\n{}", format_step(&replay, step));
+                    if step == last {
+                        println!("paused at the last step");
+                        interval = None;
+                    }
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+                // Any line pauses, including the commands
+                Ok(_) => {
+                    println!("paused at step {}", step);
+                    interval = None;
+                    continue;
+                }
+            },
             None => {
-                println!("Failed to solve {}", i);
+                print!("> ");
+                io::stdout().flush().unwrap();
+                match lines.recv() {
+                    Err(_) => {
+                        println!();
+                        break;
+                    }
+                    Ok(line) => line,
+                }
+            }
+        };
+
+        let command = match parse_view_command(&line) {
+            Err(error) => {
+                println!("{}", error);
+                continue;
+            }
+            Ok(command) => command,
+        };
+
+        let next = match command {
+            ViewCommand::Next if step == last => {
+                println!("already at the last step");
+                continue;
+            }
+            ViewCommand::Next => step + 1,
+            ViewCommand::Back if step == 0 => {
+                println!("already at the deal");
+                continue;
             }
-            Some(solution) => {
-                println!("solution {} length {}", i, solution.len());
+            ViewCommand::Back => step - 1,
+            ViewCommand::Go(target) if target > last => {
+                println!("there are only {} steps", last);
+                continue;
             }
+            ViewCommand::Go(target) => target,
+            ViewCommand::Play(_) if step == last => {
+                println!("already at the last step");
+                continue;
+            }
+            ViewCommand::Play(seconds) => {
+                interval = Some(seconds);
+                continue;
+            }
+            ViewCommand::Show => step,
+            ViewCommand::Help => {
+                print!("{}", VIEW_HELP);
+                continue;
+            }
+            ViewCommand::Quit => break,
+        };
+
+        step = next;
+        print!("\n{}", format_step(&replay, step));
+    }
+
+    (Outcome::Solved, String::new())
+}
+
+fn svg(input: &str) -> (Outcome, String) {
+    match read_deal(input) {
+        Err(errors) => (Outcome::InvalidInput, errors.join("\n")),
+        Ok(state) => (Outcome::Solved, state.svg().to_string()),
+    }
+}
+
+fn html(input: &str, options: &Options) -> (Outcome, String) {
+    match read_replay(input, options) {
+        Err(error) => error,
+        Ok(replay) => (Outcome::Solved, replay.html().to_string()),
+    }
+}
+
+// Input errors in JSON format, for the commands without a place for them in
+// their own output, like the errors of validate and the invalid deals of batch
+fn errors_json(errors: &[String]) -> String {
+    json!({ "status": "invalid", "errors": errors }).to_string()
+}
+
+fn write_output(output: &Option<PathBuf>, text: &str) -> io::Result<()> {
+    let mut text = String::from(text);
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+
+    match output {
+        None => io::stdout().write_all(text.as_bytes()),
+        Some(path) => fs::write(path, text),
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match parse_arguments(std::env::args().skip(1)) {
+        Err(error) => {
+            eprint!("error: {}\n\n{}", error, USAGE);
+            return Outcome::Usage.into();
+        }
+        Ok(None) => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
         }
+        Ok(Some(options)) => options,
+    };
 
-        // TODO write to file
+    let (outcome, mut text) = match &options.command {
+        Command::Solve(input) => solve(input, &options),
+        Command::Batch(directory) => batch(directory, &options),
+        Command::Render(input) => render(input, options.format, options.style),
+        Command::Validate(input) => validate(input, options.format),
+        Command::Generate(seed) => generate(*seed, options.moves, options.format),
+        Command::Play(input) => play(input, &options),
+        Command::View(input) => view(input, &options),
+        Command::Svg(input) => svg(input),
+        Command::Html(input) => html(input, &options),
+    };
 
-        /*
-        with open(f"solution{i}.txt", "w") as f:
-            f.write(f"{solution}")
-         */
+    // Errors about the input are reported on stderr in text mode, so they
+    // don't end up in the output file, and as JSON in the output in JSON mode
+    let reported = match options.command {
+        Command::Batch(_) => true,
+        Command::Validate(_) => options.format == Format::Json,
+        _ => false,
+    };
+    if outcome == Outcome::InvalidInput && !reported {
+        let errors: Vec<String> = text.lines().map(String::from).collect();
+        match options.format {
+            Format::Text => {
+                for error in errors {
+                    eprintln!("error: {}", error);
+                }
+                return outcome.into();
+            }
+            Format::Json => text = errors_json(&errors),
+        }
+    }
+
+    // The interactive commands have printed everything already
+    if matches!(options.command, Command::Play(_) | Command::View(_)) {
+        return outcome.into();
     }
+
+    if let Err(error) = write_output(&options.output, &text) {
+        eprintln!("error: failed to write output: {}", error);
+        return Outcome::InvalidInput.into();
+    }
+
+    outcome.into()
 }
@@ -0,0 +1,764 @@
+// A compact text notation for game states, so deals can be typed in from the
+// game screen instead of being written out as Card literals.
+//
+// Example:
+//
+//   # anything after a hash is ignored
+//   cells: RD XX
+//   foundation: F0 R3 G0 B2
+//   1: BD RD B7 R7 B6
+//   2: RD R9 G8 G7 R4
+//   3: G2 B3 B5 R5 G3
+//   4: F G9 R8
+//   5:
+//   6: GD GD R6
+//   7: B9
+//   8: GD G4
+//
+// Cards are written as a suit letter (R, G or B) followed by either a value
+// or D for the dragon of that suit. The flower card is F, and a cell used up
+// by collected dragons is XX.
+//
+// The cells and foundation lines are optional and default to empty. They are
+// followed by exactly eight column lines, listing the cards from the bottom
+// of the column (the first card dealt) to the top (the card that can be
+// moved).
+//
+// Moves are written the way GameMove is displayed, such as `column 3 to
+// cell` or `2 cards from column 1 to column 4`, and can be parsed back.
+
+use crate::card::*;
+use crate::game::GameMove;
+use crate::game_state::GameState;
+use crate::validation::Violation;
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+const CARD_DESCRIPTION: &str = "a card such as R5, GD, F or XX";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    // Both are 1-based, pointing at the start of the offending token
+    pub line: usize,
+    pub column: usize,
+    pub expected: String,
+    pub found: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: expected {}, found {}",
+            self.line, self.column, self.expected, self.found
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn suit_letter(suit: Suit) -> char {
+    match suit {
+        Suit::Special => 'F',
+        Suit::Red => 'R',
+        Suit::Green => 'G',
+        Suit::Black => 'B',
+        Suit::FaceDown => 'X',
+    }
+}
+
+fn parse_suit_letter(letter: char) -> Option<Suit> {
+    match letter {
+        'F' => Some(Suit::Special),
+        'R' => Some(Suit::Red),
+        'G' => Some(Suit::Green),
+        'B' => Some(Suit::Black),
+        'X' => Some(Suit::FaceDown),
+        _ => None,
+    }
+}
+
+pub(crate) fn parse_card(token: &str) -> Option<Card> {
+    let mut chars = token.chars();
+    let suit = parse_suit_letter(chars.next()?)?;
+    let rest = chars.as_str();
+
+    match (suit, rest) {
+        // The flower card is hardcoded to have a value of 1, see
+        // GameState::can_move_column_to_top_right_storage
+        (Suit::Special, "") => Some(Card {
+            suit,
+            value: Some(1),
+        }),
+        (Suit::FaceDown, "X") => Some(Card { suit, value: None }),
+        (Suit::Special | Suit::FaceDown, _) => None,
+        (_, "D") => Some(Card { suit, value: None }),
+        (_, value) => match value.parse::<u8>() {
+            Ok(value) if (1..=9).contains(&value) && value.to_string() == rest => Some(Card {
+                suit,
+                value: Some(value),
+            }),
+            _ => None,
+        },
+    }
+}
+
+pub(crate) fn card_token(card: &Card) -> String {
+    match (card.suit, card.value) {
+        (Suit::Special, _) => String::from("F"),
+        (Suit::FaceDown, _) => String::from("XX"),
+        (suit, None) => format!("{}D", suit_letter(suit)),
+        (suit, Some(value)) => format!("{}{}", suit_letter(suit), value),
+    }
+}
+
+fn write_card(f: &mut Formatter<'_>, card: &Card) -> fmt::Result {
+    f.write_str(&card_token(card))
+}
+
+fn describe(token: &str) -> String {
+    format!("`{}`", token)
+}
+
+// Splits a line into whitespace separated tokens, along with the 1-based
+// character column each token starts at.
+fn tokens(line: &str, first_column: usize) -> Vec<(usize, &str)> {
+    let mut result = vec![];
+    let mut start = None;
+
+    for (column, (byte_index, c)) in line.char_indices().enumerate() {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some((column, byte_index)),
+            (Some((start_column, start_byte)), true) => {
+                result.push((first_column + start_column, &line[start_byte..byte_index]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some((start_column, start_byte)) = start {
+        result.push((first_column + start_column, &line[start_byte..]));
+    }
+
+    result
+}
+
+impl FromStr for Card {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let token = s.trim();
+        parse_card(token).ok_or_else(|| ParseError {
+            line: 1,
+            column: s.chars().count() - s.trim_start().chars().count() + 1,
+            expected: String::from(CARD_DESCRIPTION),
+            found: describe(token),
+        })
+    }
+}
+
+struct Line<'a> {
+    number: usize,
+    label: &'a str,
+    label_column: usize,
+    values: Vec<(usize, &'a str)>,
+}
+
+fn parse_line(number: usize, line: &str) -> Result<Option<Line<'_>>, ParseError> {
+    let line = match line.find('#') {
+        None => line,
+        Some(comment_start) => &line[..comment_start],
+    };
+
+    if line.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let label_column = line.chars().take_while(|c| c.is_whitespace()).count() + 1;
+    let (label, values) = match line.split_once(':') {
+        None => {
+            return Err(ParseError {
+                line: number,
+                column: label_column,
+                expected: String::from("a line of the form `label: values`"),
+                found: describe(line.trim()),
+            })
+        }
+        Some(split) => split,
+    };
+
+    Ok(Some(Line {
+        number,
+        label: label.trim(),
+        label_column,
+        values: tokens(values, label.chars().count() + 2),
+    }))
+}
+
+fn parse_cards(line: &Line) -> Result<Vec<Card>, ParseError> {
+    line.values
+        .iter()
+        .map(|(column, token)| {
+            parse_card(token).ok_or_else(|| ParseError {
+                line: line.number,
+                column: *column,
+                expected: String::from(CARD_DESCRIPTION),
+                found: describe(token),
+            })
+        })
+        .collect()
+}
+
+fn parse_cells(line: &Line) -> Result<Vec<Card>, ParseError> {
+    let cards = parse_cards(line)?;
+    if cards.len() > 3 {
+        let (column, token) = line.values[3];
+        return Err(ParseError {
+            line: line.number,
+            column,
+            expected: String::from("at most 3 cells"),
+            found: describe(token),
+        });
+    }
+
+    Ok(cards)
+}
+
+fn parse_foundation(line: &Line) -> Result<[u8; 4], ParseError> {
+    let mut result = [0; 4];
+    let end_column = line
+        .values
+        .last()
+        .map_or(line.label_column, |(column, token)| {
+            column + token.chars().count()
+        });
+
+    for (i, suit) in [Suit::Special, Suit::Red, Suit::Green, Suit::Black]
+        .into_iter()
+        .enumerate()
+    {
+        let max_value = if suit == Suit::Special { 1 } else { 9 };
+        let expected = format!(
+            "a foundation level between {letter}0 and {letter}{max_value}",
+            letter = suit_letter(suit),
+        );
+
+        let (column, token) = match line.values.get(i) {
+            None => {
+                return Err(ParseError {
+                    line: line.number,
+                    column: end_column,
+                    expected,
+                    found: String::from("end of line"),
+                })
+            }
+            Some(value) => *value,
+        };
+
+        let value = token
+            .strip_prefix(suit_letter(suit))
+            .and_then(|value| value.parse::<u8>().ok())
+            .filter(|value| *value <= max_value && token.len() == 2);
+
+        match value {
+            None => {
+                return Err(ParseError {
+                    line: line.number,
+                    column,
+                    expected,
+                    found: describe(token),
+                })
+            }
+            Some(value) => result[suit as usize] = value,
+        }
+    }
+
+    if let Some((column, token)) = line.values.get(4) {
+        return Err(ParseError {
+            line: line.number,
+            column: *column,
+            expected: String::from("end of line"),
+            found: describe(token),
+        });
+    }
+
+    Ok(result)
+}
+
+impl FromStr for GameState {
+    type Err = ParseError;
+
+    // Also checks that the position could come up in the game, see
+    // GameState::validate
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let state = GameState::parse_unchecked(s)?;
+        state
+            .validate()
+            .map_err(|violations| invalid_position(s, &violations))?;
+        Ok(state)
+    }
+}
+
+// The violations are about the position as a whole, so the error points at
+// its first line
+fn invalid_position(s: &str, violations: &[Violation]) -> ParseError {
+    let line = s
+        .lines()
+        .position(|line| !line.split('#').next().unwrap().trim().is_empty())
+        .map_or(1, |i| i + 1);
+    let violations: Vec<String> = violations.iter().map(ToString::to_string).collect();
+
+    ParseError {
+        line,
+        column: 1,
+        expected: String::from("a position that can come up in the game"),
+        found: violations.join(", "),
+    }
+}
+
+impl GameState {
+    // Parses the text notation without validating the position, for partial
+    // positions such as test fixtures
+    pub fn parse_unchecked(s: &str) -> Result<GameState, ParseError> {
+        let mut state = GameState {
+            top_left_storage: vec![],
+            top_right_storage: [0; 4],
+            columns: Default::default(),
+        };
+
+        let mut lines = vec![];
+        for (i, line) in s.lines().enumerate() {
+            if let Some(line) = parse_line(i + 1, line)? {
+                lines.push(line);
+            }
+        }
+        let mut lines = lines.into_iter().peekable();
+
+        if let Some(line) = lines.next_if(|line| line.label == "cells") {
+            state.top_left_storage = parse_cells(&line)?;
+        }
+
+        if let Some(line) = lines.next_if(|line| line.label == "foundation") {
+            state.top_right_storage = parse_foundation(&line)?;
+        }
+
+        for (i, column) in state.columns.iter_mut().enumerate() {
+            let label = (i + 1).to_string();
+            let expected = match i {
+                0 => String::from("`cells:`, `foundation:` or `1:`"),
+                _ => format!("`{}:`", label),
+            };
+
+            match lines.next() {
+                None => {
+                    return Err(ParseError {
+                        line: s.lines().count() + 1,
+                        column: 1,
+                        expected,
+                        found: String::from("end of input"),
+                    })
+                }
+                Some(line) if line.label != label => {
+                    return Err(ParseError {
+                        line: line.number,
+                        column: line.label_column,
+                        expected,
+                        found: describe(&format!("{}:", line.label)),
+                    })
+                }
+                Some(line) => *column = parse_cards(&line)?,
+            }
+        }
+
+        if let Some(line) = lines.next() {
+            return Err(ParseError {
+                line: line.number,
+                column: line.label_column,
+                expected: String::from("end of input"),
+                found: describe(&format!("{}:", line.label)),
+            });
+        }
+
+        Ok(state)
+    }
+}
+
+// Formats a game state in the text notation, such that parsing the output
+// gives back an identical state
+pub struct Notation<'a>(pub &'a GameState);
+
+impl GameState {
+    pub fn notation(&self) -> Notation<'_> {
+        Notation(self)
+    }
+}
+
+impl fmt::Display for Notation<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let state = self.0;
+
+        f.write_str("cells:")?;
+        for card in &state.top_left_storage {
+            f.write_str(" ")?;
+            write_card(f, card)?;
+        }
+        f.write_str("\n")?;
+
+        f.write_str("foundation:")?;
+        for (suit, value) in state.top_right_storage.iter().enumerate() {
+            let suit = Suit::try_from(suit).unwrap();
+            write!(f, " {}{}", suit_letter(suit), value)?;
+        }
+        f.write_str("\n")?;
+
+        for (i, column) in state.columns.iter().enumerate() {
+            write!(f, "{}:", i + 1)?;
+            for card in column {
+                f.write_str(" ")?;
+                write_card(f, card)?;
+            }
+            f.write_str("\n")?;
+        }
+
+        Ok(())
+    }
+}
+
+const MOVE_DESCRIPTION: &str = "a move such as `column 3 to cell`";
+
+impl FromStr for GameMove {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokens(s, 1);
+        let words: Vec<&str> = tokens.iter().map(|(_, token)| *token).collect();
+        let error = |i: usize, expected: &str| {
+            let (column, found) = match tokens.get(i) {
+                None => (s.chars().count() + 1, String::from("end of input")),
+                Some((column, token)) => (*column, describe(token)),
+            };
+            ParseError {
+                line: 1,
+                column,
+                expected: String::from(expected),
+                found,
+            }
+        };
+        // Columns and cells are numbered from 1, see the Display implementation
+        let number = |i: usize, name: &str, count: usize| match words[i].parse::<usize>() {
+            Ok(number) if (1..=count).contains(&number) => Ok(number - 1),
+            _ => Err(error(
+                i,
+                &format!("a {} number between 1 and {}", name, count),
+            )),
+        };
+
+        let game_move = match words.as_slice() {
+            ["start"] => GameMove::Start,
+            ["column", _, "to", "foundation"] => GameMove::ColumnToTopRightStorage {
+                column: number(1, "column", 8)?,
+            },
+            ["cell", _, "to", "foundation"] => GameMove::TopLeftToTopRightStorage {
+                top_left_index: number(1, "cell", 3)?,
+            },
+            ["collect", suit, "dragons"] => GameMove::CollectDragons {
+                suit: match *suit {
+                    "Red" => Suit::Red,
+                    "Green" => Suit::Green,
+                    "Black" => Suit::Black,
+                    _ => return Err(error(1, "Red, Green or Black")),
+                },
+            },
+            [count, "card" | "cards", "from", "column", _, "to", "column", _] => {
+                GameMove::ColumnToOtherColumn {
+                    from_column_index: number(4, "column", 8)?,
+                    to_column_index: number(7, "column", 8)?,
+                    stack_size: match count.parse() {
+                        Ok(count) if count > 0 => count,
+                        _ => return Err(error(0, "a card count")),
+                    },
+                }
+            }
+            ["column", _, "to", "cell"] => GameMove::ToTopLeftStorage {
+                column: number(1, "column", 8)?,
+            },
+            ["cell", _, "to", "column", _] => GameMove::TopLeftToColumn {
+                top_left_index: number(1, "cell", 3)?,
+                column_index: number(4, "column", 8)?,
+            },
+            _ => {
+                return Err(ParseError {
+                    found: match s.trim() {
+                        "" => String::from("end of input"),
+                        text => describe(text),
+                    },
+                    ..error(0, MOVE_DESCRIPTION)
+                })
+            }
+        };
+
+        Ok(game_move)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::card::Suit::{Black, FaceDown, Green, Red, Special};
+    use googletest::assert_that;
+    use googletest::matchers::*;
+
+    const DEAL: &str = "\
+cells: RD XX
+foundation: F0 R3 G0 B2
+1: BD RD B7 R7 B6
+2: RD R9 G8 G7 R4
+3: G2 B3 B5 R5 G3
+4: F G9 R8
+5:
+6: GD GD R6
+7: B9
+8: GD G4
+";
+
+    fn parse_error(s: &str) -> ParseError {
+        s.parse::<GameState>().unwrap_err()
+    }
+
+    #[test]
+    fn test_parse_card() {
+        let test_data = [
+            ("R5", Red, Some(5)),
+            ("G1", Green, Some(1)),
+            ("B9", Black, Some(9)),
+            ("RD", Red, None),
+            ("BD", Black, None),
+            ("F", Special, Some(1)),
+            ("XX", FaceDown, None),
+        ];
+
+        for (token, suit, value) in test_data {
+            assert_that!(token.parse::<Card>(), ok(eq(Card { suit, value })));
+        }
+
+        for token in ["R0", "R10", "R+5", "FD", "X", "XD", "Q5", "r5", ""] {
+            assert_that!(token.parse::<Card>().is_err(), eq(true));
+        }
+
+        // Columns count characters, not bytes
+        assert_that!(
+            "\u{3000}\u{3000}Q5"
+                .parse::<Card>()
+                .map_err(|error| error.column),
+            err(eq(3))
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let state = GameState::parse_unchecked(DEAL).unwrap();
+
+        assert_that!(
+            state.top_left_storage,
+            eq(vec![
+                Card {
+                    suit: Red,
+                    value: None
+                },
+                Card {
+                    suit: FaceDown,
+                    value: None
+                },
+            ])
+        );
+        assert_that!(state.top_right_storage, eq([0, 3, 0, 2]));
+        assert_that!(state.columns[0].len(), eq(5));
+        assert_that!(state.columns[4].is_empty(), eq(true));
+        assert_that!(
+            state.columns[3][0],
+            eq(Card {
+                suit: Special,
+                value: Some(1)
+            })
+        );
+        assert_that!(
+            state.columns[7].last(),
+            some(eq(&Card {
+                suit: Green,
+                value: Some(4)
+            }))
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let state = GameState::parse_unchecked(DEAL).unwrap();
+        let text = state.notation().to_string();
+        assert_that!(text.as_str(), eq(DEAL));
+
+        let parsed = GameState::parse_unchecked(&text).unwrap();
+        // compare the fields directly, equality of game states merges
+        // symmetric positions
+        assert_that!(parsed.top_left_storage, eq(state.top_left_storage));
+        assert_that!(parsed.top_right_storage, eq(state.top_right_storage));
+        assert_that!(parsed.columns, eq(state.columns));
+    }
+
+    #[test]
+    fn test_optional_sections_and_comments() {
+        let state: GameState = GameState::parse_unchecked(
+            "# a fresh deal\n\n1: R1\n2:\n3:\n4:\n5:\n6:\n7:\n8: G2 # top\n",
+        )
+        .unwrap();
+
+        assert_that!(state.top_left_storage.is_empty(), eq(true));
+        assert_that!(state.top_right_storage, eq([0; 4]));
+        assert_that!(state.columns[0].len(), eq(1));
+        assert_that!(state.columns[7].len(), eq(1));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_that!(
+            parse_error("1: R1 Q7\n"),
+            eq(ParseError {
+                line: 1,
+                column: 7,
+                expected: String::from(CARD_DESCRIPTION),
+                found: String::from("`Q7`"),
+            })
+        );
+
+        assert_that!(
+            parse_error("cells: RD GD BD R1\n"),
+            eq(ParseError {
+                line: 1,
+                column: 17,
+                expected: String::from("at most 3 cells"),
+                found: String::from("`R1`"),
+            })
+        );
+
+        assert_that!(
+            parse_error("foundation: F0 R3 G10 B0\n"),
+            eq(ParseError {
+                line: 1,
+                column: 19,
+                expected: String::from("a foundation level between G0 and G9"),
+                found: String::from("`G10`"),
+            })
+        );
+
+        assert_that!(
+            parse_error("foundation: F0 R3\n"),
+            eq(ParseError {
+                line: 1,
+                column: 18,
+                expected: String::from("a foundation level between G0 and G9"),
+                found: String::from("end of line"),
+            })
+        );
+
+        assert_that!(
+            parse_error("1:\n2:\n  4: R1\n"),
+            eq(ParseError {
+                line: 3,
+                column: 3,
+                expected: String::from("`3:`"),
+                found: String::from("`4:`"),
+            })
+        );
+
+        assert_that!(
+            parse_error("1:\n2:\n3:\n"),
+            eq(ParseError {
+                line: 4,
+                column: 1,
+                expected: String::from("`4:`"),
+                found: String::from("end of input"),
+            })
+        );
+
+        assert_that!(
+            parse_error("1 R1\n"),
+            eq(ParseError {
+                line: 1,
+                column: 1,
+                expected: String::from("a line of the form `label: values`"),
+                found: String::from("`1 R1`"),
+            })
+        );
+
+        assert_that!(
+            parse_error("1:\n2:\n3:\n4:\n5:\n6:\n7:\n8:\n9:\n"),
+            eq(ParseError {
+                line: 9,
+                column: 1,
+                expected: String::from("end of input"),
+                found: String::from("`9:`"),
+            })
+        );
+
+        assert_that!(
+            parse_error("1: R1 Q7\n").to_string().as_str(),
+            eq("line 1, column 7: expected a card such as R5, GD, F or XX, found `Q7`")
+        );
+    }
+
+    #[test]
+    fn test_invalid_position() {
+        let text = crate::deal::deal(0).notation().to_string();
+        assert_that!(text.parse::<GameState>(), ok(anything()));
+
+        // Swap a card for a second copy of another one
+        let text = text.replacen("R9", "R8", 1);
+        let error = parse_error(&format!("# one card twice\n\n{}", text));
+        assert_that!(error.line, eq(3));
+        assert_that!(
+            error.expected.as_str(),
+            eq("a position that can come up in the game")
+        );
+        assert_that!(error.found.is_empty(), eq(false));
+        assert_that!(GameState::parse_unchecked(&text), ok(anything()));
+    }
+
+    #[test]
+    fn test_parse_move() {
+        let mut moves = vec![GameMove::Start];
+        for seed in 0..20 {
+            moves.extend(crate::deal::random_position(seed, 30).legal_moves());
+        }
+        for game_move in moves {
+            assert_that!(
+                game_move.to_string().parse::<GameMove>(),
+                ok(eq(game_move.clone()))
+            );
+        }
+
+        assert_that!(
+            "cell 4 to column 2".parse::<GameMove>(),
+            err(eq(ParseError {
+                line: 1,
+                column: 6,
+                expected: String::from("a cell number between 1 and 3"),
+                found: String::from("`4`"),
+            }))
+        );
+        assert_that!(
+            "column 3 to somewhere".parse::<GameMove>(),
+            err(eq(ParseError {
+                line: 1,
+                column: 1,
+                expected: String::from(MOVE_DESCRIPTION),
+                found: String::from("`column 3 to somewhere`"),
+            }))
+        );
+        assert_that!(
+            "0 cards from column 1 to column 2".parse::<GameMove>(),
+            err(anything())
+        );
+        assert_that!("collect red dragons".parse::<GameMove>(), err(anything()));
+    }
+}
@@ -0,0 +1,61 @@
+// Control over a running search from the outside, for front ends that run
+// the solver on a background thread.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Stops a search from another thread. Clones share the same flag, so keep
+// one and pass a clone to Game::with_cancellation.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    // The search stops before expanding its next node
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+// A snapshot of a running search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    pub expanded_nodes: usize,
+    // Waiting to be expanded
+    pub open_states: usize,
+    // Every state seen so far
+    pub stored_states: usize,
+    // The highest priority of the nodes expanded so far. This is the
+    // heuristic score for the greedy search, and minus the estimated solution
+    // length for the optimal search.
+    pub best_priority: i32,
+    pub elapsed: Duration,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use googletest::assert_that;
+    use googletest::matchers::*;
+
+    #[test]
+    fn test_cancellation_token() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert_that!(clone.is_cancelled(), eq(false));
+
+        token.cancel();
+        assert_that!(clone.is_cancelled(), eq(true));
+        assert_that!(CancellationToken::new().is_cancelled(), eq(false));
+    }
+}
@@ -0,0 +1,281 @@
+// Drawing cards and positions as text for people to read, in one of a few
+// styles. Display on Card and GameState draws ASCII, or ANSI with the
+// alternate flag, as in `println!("{:#}", state)`. Any style, emoji included,
+// can be picked with render, as in `println!("{}", state.render(Style::Emoji))`.
+//
+// A position is drawn like the game table, the cells and the foundation above
+// the columns, each card in the same place as on screen:
+//
+//   RD XX .     F  R3 .  B2
+//   1  2  3  4  5  6  7  8
+//   BD RD G2 F  .  GD B9 GD
+//   RD R9 B3 G9    GD    G4
+//
+// Dots are free cells, empty foundations and empty columns.
+
+use crate::card::*;
+use crate::game_state::GameState;
+use crate::notation::card_token;
+use std::fmt;
+use std::fmt::Formatter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Style {
+    // The tokens of the text notation, such as R5, GD, F and XX
+    #[default]
+    Ascii,
+    // The same tokens, coloured with ANSI escape codes for terminals
+    Ansi,
+    // Coloured squares. Each square takes two columns in most terminals, but
+    // not all of them, so the layout can come out ragged.
+    Emoji,
+}
+
+impl Style {
+    // The columns taken by a card, not counting escape codes
+    fn card_width(self) -> usize {
+        match self {
+            Style::Ascii | Style::Ansi => 2,
+            Style::Emoji => 3,
+        }
+    }
+}
+
+pub struct Styled<'a, T>(pub &'a T, pub Style);
+
+impl Card {
+    pub fn render(&self, style: Style) -> Styled<'_, Card> {
+        Styled(self, style)
+    }
+}
+
+impl GameState {
+    pub fn render(&self, style: Style) -> Styled<'_, GameState> {
+        Styled(self, style)
+    }
+}
+
+fn ansi_colour(suit: Suit) -> &'static str {
+    match suit {
+        Suit::Special => "35",
+        Suit::Red => "31",
+        Suit::Green => "32",
+        // Bold in the default colour, black would disappear on a dark
+        // background
+        Suit::Black => "1",
+        Suit::FaceDown => "2",
+    }
+}
+
+impl fmt::Display for Styled<'_, Card> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Styled(card, style) = *self;
+        match style {
+            Style::Ascii => f.write_str(&card_token(card)),
+            Style::Ansi => write!(
+                f,
+                "\x1b[{}m{}\x1b[0m",
+                ansi_colour(card.suit),
+                card_token(card)
+            ),
+            Style::Emoji => {
+                let colours = ["🟦", "🟥", "🟩", "⬛"];
+                match (card.suit, card.value) {
+                    (Suit::FaceDown, _) => f.write_str("xxx"),
+                    (suit, None) => write!(f, "{}x", colours[suit as usize]),
+                    (suit, Some(value)) => write!(f, "{}{}", colours[suit as usize], value),
+                }
+            }
+        }
+    }
+}
+
+// A card, or a dot for an empty place, padded to the width of a card
+fn slot(card: Option<&Card>, style: Style) -> String {
+    let (text, width) = match (card, style) {
+        (None, _) => (String::from("."), 1),
+        (Some(card), Style::Emoji) => (card.render(style).to_string(), 3),
+        (Some(card), _) => (card.render(style).to_string(), card_token(card).len()),
+    };
+    text + &" ".repeat(style.card_width() - width)
+}
+
+fn write_row(f: &mut Formatter<'_>, slots: &[String]) -> fmt::Result {
+    writeln!(f, "{}", slots.join(" ").trim_end())
+}
+
+impl fmt::Display for Styled<'_, GameState> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Styled(state, style) = *self;
+        let blank = " ".repeat(style.card_width());
+
+        // The cells are above the first three columns, and the foundation
+        // above the last four, leaving a gap where the game has the dragon
+        // buttons
+        let mut top = vec![];
+        for cell in 0..3 {
+            top.push(slot(state.top_left_storage.get(cell), style));
+        }
+        top.push(blank.clone());
+        for suit in [Suit::Special, Suit::Red, Suit::Green, Suit::Black] {
+            let card = match state.top_right_storage[suit as usize] {
+                0 => None,
+                value => Some(Card {
+                    suit,
+                    value: Some(value),
+                }),
+            };
+            top.push(slot(card.as_ref(), style));
+        }
+        write_row(f, &top)?;
+
+        let labels: Vec<String> = (1..=8)
+            .map(|label| format!("{:<1$}", label, style.card_width()))
+            .collect();
+        write_row(f, &labels)?;
+
+        let rows = state.columns.iter().map(Vec::len).max().unwrap_or(0).max(1);
+        for row in 0..rows {
+            let slots: Vec<String> = state
+                .columns
+                .iter()
+                .map(|column| match column.get(row) {
+                    None if row == 0 => slot(None, style),
+                    None => blank.clone(),
+                    Some(card) => slot(Some(card), style),
+                })
+                .collect();
+            write_row(f, &slots)?;
+        }
+
+        Ok(())
+    }
+}
+
+// The style for Display, `{}` is the default style and `{:#}` is ANSI
+fn display_style(f: &Formatter<'_>) -> Style {
+    if f.alternate() {
+        Style::Ansi
+    } else {
+        Style::default()
+    }
+}
+
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.render(display_style(f)).fmt(f)
+    }
+}
+
+impl fmt::Display for GameState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.render(display_style(f)).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use googletest::assert_that;
+    use googletest::matchers::*;
+
+    const DEAL: &str = "\
+cells: RD XX
+foundation: F1 R3 G0 B2
+1: BD RD B7 R7 B6
+2: RD R9 G8 G7 R4
+3: G2 B3 B5 R5 G3
+4: F G9 R8
+5:
+6: GD GD R6
+7: B9
+8: GD G4
+";
+
+    fn state() -> GameState {
+        GameState::parse_unchecked(DEAL).unwrap()
+    }
+
+    // Drops the ANSI escape codes, leaving what the terminal shows
+    fn strip_escapes(text: &str) -> String {
+        let mut result = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\x1b' => {
+                    chars.by_ref().find(|c| *c == 'm');
+                }
+                c => result.push(c),
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn test_ascii() {
+        assert_that!(
+            state().render(Style::Ascii).to_string(),
+            eq("\
+RD XX .     F  R3 .  B2
+1  2  3  4  5  6  7  8
+BD RD G2 F  .  GD B9 GD
+RD R9 B3 G9    GD    G4
+B7 G8 B5 R8    R6
+R7 G7 R5
+B6 R4 G3
+")
+        );
+        assert_that!(
+            state().to_string(),
+            eq(state().render(Style::Ascii).to_string())
+        );
+    }
+
+    #[test]
+    fn test_ansi() {
+        let ansi = state().render(Style::Ansi).to_string();
+        assert_that!(ansi, not(eq(state().render(Style::Ascii).to_string())));
+        assert_that!(
+            strip_escapes(&ansi),
+            eq(state().render(Style::Ascii).to_string())
+        );
+        assert_that!(format!("{:#}", state()), eq(ansi));
+    }
+
+    #[test]
+    fn test_cards() {
+        let test_data = [
+            (Suit::Red, Some(5), "R5", "🟥5"),
+            (Suit::Green, None, "GD", "🟩x"),
+            (Suit::Special, Some(1), "F", "🟦1"),
+            (Suit::FaceDown, None, "XX", "xxx"),
+        ];
+
+        for (suit, value, ascii, emoji) in test_data {
+            let card = Card { suit, value };
+            assert_that!(card.to_string(), eq(ascii));
+            assert_that!(
+                format!("{:#}", card),
+                eq(card.render(Style::Ansi).to_string())
+            );
+            assert_that!(card.render(Style::Emoji).to_string(), eq(emoji));
+            assert_that!(
+                strip_escapes(&card.render(Style::Ansi).to_string()),
+                eq(ascii)
+            );
+        }
+    }
+
+    #[test]
+    fn test_emoji_columns_line_up() {
+        let text = state().render(Style::Emoji).to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        // Every card takes three columns, with a space between them
+        assert_that!(lines[1], eq("1   2   3   4   5   6   7   8"));
+        assert_that!(lines[2], eq("⬛x 🟥x 🟩2 🟦1 .   🟩x ⬛9 🟩x"));
+        assert_that!(
+            lines[0].starts_with("🟥x xxx .       🟦1 🟥3 .   ⬛2"),
+            eq(true)
+        );
+    }
+}
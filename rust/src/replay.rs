@@ -0,0 +1,312 @@
+// A solution worked out move by move, for front ends that show it one step at
+// a time. Each step records which cards moved and where from and to, for the
+// move the player makes and for each move the game then makes by itself.
+
+use crate::automatic::AutomaticMoves;
+use crate::card::*;
+use crate::game::GameMove;
+use crate::game_state::GameState;
+use crate::undo::Undo;
+use crate::verify::{replay_moves, VerifyError};
+
+// Where a card is on the table. Cells are numbered as they were before the
+// move, as a card leaving a cell moves the cards after it along.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Place {
+    Column(usize),
+    Cell(usize),
+    Foundation(Suit),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movement {
+    pub game_move: GameMove,
+    // From the bottom to the top of a moved stack
+    pub cards: Vec<Card>,
+    // Collecting dragons takes them from several places
+    pub from: Vec<Place>,
+    pub to: Place,
+}
+
+impl Movement {
+    // record is the result of making the move on before. The moves the game
+    // makes by itself keep their card in the record, so for those before can
+    // be any earlier position.
+    fn new(before: &GameState, record: &Undo) -> Movement {
+        let top = |column: usize| *before.columns[column].last().unwrap();
+        let (cards, from, to) = match *record {
+            Undo::Start => unreachable!("Replay::new skips GameMove::Start"),
+            Undo::ColumnToTopRightStorage { column, card, .. } => (
+                vec![card],
+                vec![Place::Column(column)],
+                Place::Foundation(card.suit),
+            ),
+            Undo::TopLeftToTopRightStorage {
+                top_left_index,
+                card,
+                ..
+            } => (
+                vec![card],
+                vec![Place::Cell(top_left_index)],
+                Place::Foundation(card.suit),
+            ),
+            Undo::CollectDragons {
+                suit,
+                ref columns,
+                ref top_left_indices,
+                top_left_index,
+            } => (
+                vec![Card { suit, value: None }; 4],
+                columns
+                    .iter()
+                    .map(|column| Place::Column(*column))
+                    .chain(top_left_indices.iter().map(|cell| Place::Cell(*cell)))
+                    .collect(),
+                Place::Cell(top_left_index),
+            ),
+            Undo::ColumnToOtherColumn {
+                from_column_index,
+                to_column_index,
+                stack_size,
+            } => {
+                let column = &before.columns[from_column_index];
+                (
+                    column[column.len() - stack_size..].to_vec(),
+                    vec![Place::Column(from_column_index)],
+                    Place::Column(to_column_index),
+                )
+            }
+            Undo::ToTopLeftStorage {
+                column,
+                top_left_index,
+            } => (
+                vec![top(column)],
+                vec![Place::Column(column)],
+                Place::Cell(top_left_index),
+            ),
+            Undo::TopLeftToColumn {
+                top_left_index,
+                column_index,
+            } => (
+                vec![before.top_left_storage[top_left_index]],
+                vec![Place::Cell(top_left_index)],
+                Place::Column(column_index),
+            ),
+        };
+
+        Movement {
+            game_move: record.game_move(),
+            cards,
+            from,
+            to,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayStep {
+    pub movement: Movement,
+    // The moves the game made by itself afterwards, in order
+    pub automatic: Vec<Movement>,
+    // The position after all of them
+    pub state: GameState,
+}
+
+impl ReplayStep {
+    // Where the cards of the player's move are once the game has made its own
+    // moves, and how many of them are still there. The game can take them on
+    // to the foundation, or move a card along in the cells. None when none of
+    // them are left.
+    pub fn landed(&self) -> Option<(Place, usize)> {
+        let movement = &self.movement;
+        match movement.to {
+            Place::Column(column) => {
+                let taken = self
+                    .automatic
+                    .iter()
+                    .filter(|automatic| automatic.from.contains(&movement.to))
+                    .count();
+                let count = movement.cards.len().saturating_sub(taken);
+                (count > 0).then_some((Place::Column(column), count))
+            }
+            Place::Cell(mut cell) => {
+                for automatic in &self.automatic {
+                    for place in &automatic.from {
+                        match *place {
+                            Place::Cell(from) if from < cell => cell -= 1,
+                            Place::Cell(from) if from == cell => return None,
+                            _ => {}
+                        }
+                    }
+                }
+                Some((Place::Cell(cell), 1))
+            }
+            Place::Foundation(suit) => Some((Place::Foundation(suit), 1)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Replay {
+    // The position before the first move, after any moves the game made by
+    // itself while dealing
+    pub start: GameState,
+    pub steps: Vec<ReplayStep>,
+}
+
+impl Replay {
+    // Checks every move like verify_moves, but the moves don't have to solve
+    // the game. A leading GameMove::Start, as in the solutions from
+    // Game::play, is skipped.
+    pub fn new(
+        start: &GameState,
+        moves: &[GameMove],
+        automatic_moves: AutomaticMoves,
+    ) -> Result<Replay, VerifyError> {
+        let mut steps = vec![];
+        let (start, _) = replay_moves(start, moves.iter(), automatic_moves, |step| {
+            // replay_moves only allows GameMove::Start as the first move
+            if *step.game_move == GameMove::Start {
+                return Ok(());
+            }
+
+            steps.push(ReplayStep {
+                movement: Movement::new(step.before, step.record),
+                automatic: step
+                    .automatic
+                    .iter()
+                    .map(|record| Movement::new(step.before, record))
+                    .collect(),
+                state: step.state.clone(),
+            });
+            Ok(())
+        })?;
+
+        Ok(Replay { start, steps })
+    }
+
+    // The position after the given number of steps
+    pub fn state(&self, step: usize) -> &GameState {
+        match step {
+            0 => &self.start,
+            _ => &self.steps[step - 1].state,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::Game;
+    use crate::verify::{identical, VerifyErrorKind};
+    use googletest::matchers::*;
+    use googletest::{assert_that, matches_pattern};
+
+    fn start() -> GameState {
+        include_str!("../deals/deal0.txt").parse().unwrap()
+    }
+
+    #[test]
+    fn test_solution() {
+        let solution = Game::new().play(start()).unwrap();
+        let moves: Vec<GameMove> = solution.iter().map(|(_, m)| m.clone()).collect();
+        let replay = Replay::new(&start(), &moves, AutomaticMoves::Emulated).unwrap();
+
+        assert_that!(replay.steps.len(), eq(solution.len() - 1));
+        for (step, (state, _)) in solution.iter().enumerate() {
+            assert_that!(identical(replay.state(step), state), eq(true));
+        }
+        assert_that!(
+            replay
+                .steps
+                .iter()
+                .map(|step| step.automatic.len())
+                .sum::<usize>(),
+            gt(0)
+        );
+    }
+
+    #[test]
+    fn test_movements() {
+        let start: GameState = "cells: GD\n\
+             foundation: F1 R1 G1 B1\n\
+             1: B9 R8 G7\n\
+             2: R2 R3 R4 R5 RD\n\
+             3: G2 G3 G4 G5 RD\n\
+             4: B3 B4 B5 B6 GD GD GD BD BD BD BD\n\
+             5: B2 RD\n\
+             6:\n\
+             7: R6 R7 R9 G6 G8 G9 B7 B8\n\
+             8: RD\n"
+            .parse()
+            .unwrap();
+        let moves = [
+            GameMove::ColumnToOtherColumn {
+                from_column_index: 0,
+                to_column_index: 5,
+                stack_size: 2,
+            },
+            GameMove::CollectDragons { suit: Suit::Red },
+        ];
+        let replay = Replay::new(&start, &moves, AutomaticMoves::Emulated).unwrap();
+
+        let first = &replay.steps[0];
+        assert_that!(
+            first.movement.cards,
+            eq(vec![
+                Card {
+                    suit: Suit::Red,
+                    value: Some(8)
+                },
+                Card {
+                    suit: Suit::Green,
+                    value: Some(7)
+                },
+            ])
+        );
+        assert_that!(first.movement.from, eq(vec![Place::Column(0)]));
+        assert_that!(first.movement.to, eq(Place::Column(5)));
+        assert_that!(first.automatic, empty());
+        assert_that!(first.landed(), some(eq((Place::Column(5), 2))));
+
+        // Collecting the dragons frees B2, which the game then collects
+        let second = &replay.steps[1];
+        assert_that!(
+            second.movement.from,
+            eq(vec![
+                Place::Column(1),
+                Place::Column(2),
+                Place::Column(4),
+                Place::Column(7)
+            ])
+        );
+        assert_that!(second.movement.to, eq(Place::Cell(1)));
+        let automatic: Vec<(Vec<Place>, Place)> = second
+            .automatic
+            .iter()
+            .map(|movement| (movement.from.clone(), movement.to))
+            .collect();
+        assert_that!(
+            automatic,
+            eq(vec![(
+                vec![Place::Column(4)],
+                Place::Foundation(Suit::Black)
+            )])
+        );
+        assert_that!(second.landed(), some(eq((Place::Cell(1), 1))));
+    }
+
+    #[test]
+    fn test_illegal_move() {
+        let moves = [GameMove::TopLeftToColumn {
+            top_left_index: 0,
+            column_index: 0,
+        }];
+        let error = Replay::new(&start(), &moves, AutomaticMoves::Emulated).unwrap_err();
+        assert_that!(error.step, eq(0));
+        assert_that!(
+            error.kind,
+            matches_pattern!(VerifyErrorKind::IllegalMove(anything()))
+        );
+    }
+}
@@ -0,0 +1,730 @@
+// Checked move application. The move_* functions on GameState assume the
+// matching can_move_* function was called first and panic otherwise, which is
+// fine for the solver but not for anything that applies moves it didn't
+// generate itself.
+
+use crate::card::*;
+use crate::game::GameMove;
+use crate::game_state::*;
+use crate::notation::card_token;
+use crate::undo::Undo;
+use std::fmt;
+use std::fmt::Formatter;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveError {
+    ColumnOutOfRange {
+        column: usize,
+    },
+    CellOutOfRange {
+        cell: usize,
+    },
+    EmptyColumn {
+        column: usize,
+    },
+    EmptyCell {
+        cell: usize,
+    },
+    NoFreeCell,
+    SameColumn {
+        column: usize,
+    },
+    // More cards were requested than are stacked in descending, alternating
+    // order at the top of the column
+    StackTooLarge {
+        column: usize,
+        stack_size: usize,
+        movable: usize,
+    },
+    IllegalTarget {
+        card: Card,
+        target: Card,
+    },
+    // The foundation for the card's suit is not one below the card, or the
+    // card is a dragon
+    NotNextFoundationCard {
+        card: Card,
+    },
+    CollectedDragonsCannotMove {
+        cell: usize,
+    },
+    NotADragonSuit {
+        suit: Suit,
+    },
+    // All four dragons must be on top of a column or in a cell
+    DragonsNotFree {
+        suit: Suit,
+        free: usize,
+    },
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::ColumnOutOfRange { column } => {
+                write!(f, "there is no column {}", column + 1)
+            }
+            MoveError::CellOutOfRange { cell } => write!(f, "there is no cell {}", cell + 1),
+            MoveError::EmptyColumn { column } => write!(f, "column {} is empty", column + 1),
+            MoveError::EmptyCell { cell } => write!(f, "cell {} is empty", cell + 1),
+            MoveError::NoFreeCell => f.write_str("all the cells are in use"),
+            MoveError::SameColumn { column } => {
+                write!(f, "column {} is both the source and target", column + 1)
+            }
+            MoveError::StackTooLarge {
+                column,
+                stack_size,
+                movable,
+            } => write!(
+                f,
+                "can't move {} cards from column {}, only the top {} form a stack",
+                stack_size,
+                column + 1,
+                movable
+            ),
+            MoveError::IllegalTarget { card, target } => write!(
+                f,
+                "{} can't be placed on {}",
+                card_token(card),
+                card_token(target)
+            ),
+            MoveError::NotNextFoundationCard { card } => {
+                write!(f, "{} can't go to the foundation yet", card_token(card))
+            }
+            MoveError::CollectedDragonsCannotMove { cell } => {
+                write!(f, "cell {} holds collected dragons", cell + 1)
+            }
+            MoveError::NotADragonSuit { suit } => write!(f, "{:?} has no dragons", suit),
+            MoveError::DragonsNotFree { suit, free } => write!(
+                f,
+                "only {} of the {:?} dragons are free to collect",
+                free, suit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+impl GameState {
+    fn check_column(&self, column: usize) -> Result<(), MoveError> {
+        if column >= self.columns.len() {
+            return Err(MoveError::ColumnOutOfRange { column });
+        }
+        Ok(())
+    }
+
+    fn check_column_not_empty(&self, column: usize) -> Result<&Card, MoveError> {
+        self.check_column(column)?;
+        self.columns[column]
+            .last()
+            .ok_or(MoveError::EmptyColumn { column })
+    }
+
+    fn check_cell(&self, cell: usize) -> Result<&Card, MoveError> {
+        if cell >= 3 {
+            return Err(MoveError::CellOutOfRange { cell });
+        }
+        self.top_left_storage
+            .get(cell)
+            .ok_or(MoveError::EmptyCell { cell })
+    }
+
+    fn check_foundation(&self, card: &Card) -> Result<(), MoveError> {
+        match card.value {
+            Some(value)
+                if card.suit != Suit::FaceDown
+                    && value > 0
+                    && self.top_right_storage[card.suit as usize] == value - 1 =>
+            {
+                Ok(())
+            }
+            _ => Err(MoveError::NotNextFoundationCard { card: *card }),
+        }
+    }
+
+    fn check_target(&self, card: &Card, column: usize) -> Result<(), MoveError> {
+        match self.columns[column].last() {
+            None => Ok(()),
+            Some(target) if card.can_be_moved_on_top_of(target) => Ok(()),
+            Some(target) => Err(MoveError::IllegalTarget {
+                card: *card,
+                target: *target,
+            }),
+        }
+    }
+
+    // Agrees with the can_move_* functions, but explains why a move is not
+    // allowed. Doesn't panic on states that pass GameState::validate, even
+    // for out of range indices. Other states, such as one with a face down
+    // card in a column, can make it panic.
+    pub fn check_move(&self, game_move: &GameMove) -> Result<(), MoveError> {
+        match *game_move {
+            GameMove::Start => Ok(()),
+            GameMove::ColumnToTopRightStorage { column } => {
+                let card = self.check_column_not_empty(column)?;
+                self.check_foundation(card)
+            }
+            GameMove::TopLeftToTopRightStorage { top_left_index } => {
+                let card = self.check_cell(top_left_index)?;
+                self.check_foundation(card)
+            }
+            GameMove::CollectDragons { suit } => {
+                if ![Suit::Red, Suit::Green, Suit::Black].contains(&suit) {
+                    return Err(MoveError::NotADragonSuit { suit });
+                }
+
+                // Dragons in a cell free up their own cell when collected
+                let dragon = Card { suit, value: None };
+                if self
+                    .top_left_storage
+                    .iter()
+                    .filter(|card| **card != dragon)
+                    .count()
+                    >= 3
+                {
+                    return Err(MoveError::NoFreeCell);
+                }
+
+                let free = self
+                    .columns
+                    .iter()
+                    .filter_map(|column| column.last())
+                    .chain(&self.top_left_storage)
+                    .filter(|card| **card == dragon)
+                    .count();
+                if free != 4 {
+                    return Err(MoveError::DragonsNotFree { suit, free });
+                }
+
+                Ok(())
+            }
+            GameMove::ColumnToOtherColumn {
+                from_column_index,
+                to_column_index,
+                stack_size,
+            } => {
+                self.check_column_not_empty(from_column_index)?;
+                self.check_column(to_column_index)?;
+                if from_column_index == to_column_index {
+                    return Err(MoveError::SameColumn {
+                        column: from_column_index,
+                    });
+                }
+
+                let movable = self.get_column_stack_size(from_column_index);
+                if stack_size == 0 || stack_size > movable {
+                    return Err(MoveError::StackTooLarge {
+                        column: from_column_index,
+                        stack_size,
+                        movable,
+                    });
+                }
+
+                let column = &self.columns[from_column_index];
+                self.check_target(&column[column.len() - stack_size], to_column_index)
+            }
+            GameMove::ToTopLeftStorage { column } => {
+                self.check_column_not_empty(column)?;
+                if self.top_left_storage.len() >= 3 {
+                    return Err(MoveError::NoFreeCell);
+                }
+                Ok(())
+            }
+            GameMove::TopLeftToColumn {
+                top_left_index,
+                column_index,
+            } => {
+                let card = self.check_cell(top_left_index)?;
+                if card.suit == Suit::FaceDown {
+                    return Err(MoveError::CollectedDragonsCannotMove {
+                        cell: top_left_index,
+                    });
+                }
+                self.check_column(column_index)?;
+                self.check_target(card, column_index)
+            }
+        }
+    }
+
+    // Applies a move after checking that it is legal. The state is left
+    // unchanged if it isn't. GameMove::Start marks the initial position in a
+    // solution, and does nothing.
+    //
+    // The returned record can be passed to undo to restore the previous state.
+    pub fn apply(&mut self, game_move: &GameMove) -> Result<Undo, MoveError> {
+        self.check_move(game_move)?;
+        Ok(self.apply_unchecked(game_move))
+    }
+
+    // Like the move_* functions, this assumes the move is legal, e.g. because
+    // it came from legal_moves
+    pub fn apply_unchecked(&mut self, game_move: &GameMove) -> Undo {
+        let undo = self.undo_record(game_move);
+
+        match *game_move {
+            GameMove::Start => {}
+            GameMove::ColumnToTopRightStorage { column } => {
+                self.move_column_to_top_right_storage(column)
+            }
+            GameMove::TopLeftToTopRightStorage { top_left_index } => {
+                self.move_top_left_to_top_right_storage(top_left_index)
+            }
+            GameMove::CollectDragons { suit } => self.collect_dragons(suit),
+            GameMove::ColumnToOtherColumn {
+                from_column_index,
+                to_column_index,
+                stack_size,
+            } => self.move_column_to_other_column(MoveColumnParameters {
+                from_column_index,
+                to_column_index,
+                stack_size,
+            }),
+            GameMove::ToTopLeftStorage { column } => self.move_column_to_top_left(column),
+            GameMove::TopLeftToColumn {
+                top_left_index,
+                column_index,
+            } => self.move_top_left_to_column(top_left_index, column_index),
+        }
+
+        undo
+    }
+
+    // Every legal move from this position. The order is always the same:
+    // moves to the foundation, collecting dragons, moves between columns
+    // (largest stacks first), moves to a cell and finally moves out of a cell.
+    pub fn legal_moves(&self) -> Vec<GameMove> {
+        let mut moves = vec![];
+
+        for column in 0..8 {
+            if self.can_move_column_to_top_right_storage(column) {
+                moves.push(GameMove::ColumnToTopRightStorage { column });
+            }
+        }
+
+        for top_left_index in 0..3 {
+            if self.can_move_top_left_to_top_right_storage(top_left_index) {
+                moves.push(GameMove::TopLeftToTopRightStorage { top_left_index });
+            }
+        }
+
+        for suit in [Suit::Red, Suit::Green, Suit::Black] {
+            if self.can_collect_dragons(suit) {
+                moves.push(GameMove::CollectDragons { suit });
+            }
+        }
+
+        for from_column_index in 0..8 {
+            for to_column_index in 0..8 {
+                if from_column_index == to_column_index {
+                    continue;
+                }
+
+                for stack_size in (1..9 + 1).rev() {
+                    if self.can_move_column_to_other_column(MoveColumnParameters {
+                        from_column_index,
+                        to_column_index,
+                        stack_size,
+                    }) {
+                        moves.push(GameMove::ColumnToOtherColumn {
+                            from_column_index,
+                            to_column_index,
+                            stack_size,
+                        });
+                    }
+                }
+            }
+        }
+
+        for column in 0..8 {
+            if self.can_move_column_to_top_left(column) {
+                moves.push(GameMove::ToTopLeftStorage { column });
+            }
+        }
+
+        for top_left_index in 0..3 {
+            for column_index in 0..8 {
+                if self.can_move_top_left_to_column(top_left_index, column_index) {
+                    moves.push(GameMove::TopLeftToColumn {
+                        top_left_index,
+                        column_index,
+                    });
+                }
+            }
+        }
+
+        moves
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::card::Suit::{Black, FaceDown, Green, Red, Special};
+    use crate::deal::random_position;
+    use googletest::assert_that;
+    use googletest::matchers::*;
+
+    fn state() -> GameState {
+        GameState::parse_unchecked(
+            "cells: RD XX G5\n\
+         foundation: F0 R2 G3 B1\n\
+         1: B9 R8\n\
+         2: G9 B6 R5 B4\n\
+         3:\n\
+         4: RD RD\n\
+         5: F\n\
+         6: GD\n\
+         7: RD B2\n\
+         8: G4\n",
+        )
+        .unwrap()
+    }
+
+    fn every_move() -> Vec<GameMove> {
+        let mut moves = vec![];
+        for column in 0..8 {
+            moves.push(GameMove::ColumnToTopRightStorage { column });
+            moves.push(GameMove::ToTopLeftStorage { column });
+        }
+        for top_left_index in 0..3 {
+            moves.push(GameMove::TopLeftToTopRightStorage { top_left_index });
+            for column_index in 0..8 {
+                moves.push(GameMove::TopLeftToColumn {
+                    top_left_index,
+                    column_index,
+                });
+            }
+        }
+        for suit in [Red, Green, Black] {
+            moves.push(GameMove::CollectDragons { suit });
+        }
+        for from_column_index in 0..8 {
+            for to_column_index in 0..8 {
+                if from_column_index == to_column_index {
+                    continue;
+                }
+                for stack_size in 1..=9 {
+                    moves.push(GameMove::ColumnToOtherColumn {
+                        from_column_index,
+                        to_column_index,
+                        stack_size,
+                    });
+                }
+            }
+        }
+        moves
+    }
+
+    fn can_move(state: &GameState, game_move: &GameMove) -> bool {
+        match *game_move {
+            GameMove::Start => true,
+            GameMove::ColumnToTopRightStorage { column } => {
+                state.can_move_column_to_top_right_storage(column)
+            }
+            GameMove::TopLeftToTopRightStorage { top_left_index } => {
+                state.can_move_top_left_to_top_right_storage(top_left_index)
+            }
+            GameMove::CollectDragons { suit } => state.can_collect_dragons(suit),
+            GameMove::ColumnToOtherColumn {
+                from_column_index,
+                to_column_index,
+                stack_size,
+            } => state.can_move_column_to_other_column(MoveColumnParameters {
+                from_column_index,
+                to_column_index,
+                stack_size,
+            }),
+            GameMove::ToTopLeftStorage { column } => state.can_move_column_to_top_left(column),
+            GameMove::TopLeftToColumn {
+                top_left_index,
+                column_index,
+            } => state.can_move_top_left_to_column(top_left_index, column_index),
+        }
+    }
+
+    #[test]
+    fn test_agrees_with_can_move() {
+        for seed in 0..20 {
+            for move_count in [0, 10, 40] {
+                let state = random_position(seed, move_count);
+                for game_move in every_move() {
+                    assert_that!(
+                        state.check_move(&game_move).is_ok(),
+                        eq(can_move(&state, &game_move))
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_legal_moves() {
+        for seed in 0..20 {
+            for move_count in [0, 10, 40] {
+                let state = random_position(seed, move_count);
+                let legal_moves = state.legal_moves();
+
+                let expected: Vec<GameMove> = every_move()
+                    .into_iter()
+                    .filter(|game_move| state.check_move(game_move).is_ok())
+                    .collect();
+                assert_that!(legal_moves.len(), eq(expected.len()));
+                for game_move in &expected {
+                    assert_that!(legal_moves, contains(eq(game_move.clone())));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_legal_moves_order() {
+        assert_that!(
+            state().legal_moves(),
+            eq(vec![
+                GameMove::ColumnToTopRightStorage { column: 4 },
+                GameMove::ColumnToTopRightStorage { column: 6 },
+                GameMove::ColumnToTopRightStorage { column: 7 },
+                GameMove::ColumnToOtherColumn {
+                    from_column_index: 0,
+                    to_column_index: 2,
+                    stack_size: 2
+                },
+                GameMove::ColumnToOtherColumn {
+                    from_column_index: 0,
+                    to_column_index: 2,
+                    stack_size: 1
+                },
+                GameMove::ColumnToOtherColumn {
+                    from_column_index: 1,
+                    to_column_index: 2,
+                    stack_size: 3
+                },
+                GameMove::ColumnToOtherColumn {
+                    from_column_index: 1,
+                    to_column_index: 2,
+                    stack_size: 2
+                },
+                GameMove::ColumnToOtherColumn {
+                    from_column_index: 1,
+                    to_column_index: 2,
+                    stack_size: 1
+                },
+                GameMove::ColumnToOtherColumn {
+                    from_column_index: 3,
+                    to_column_index: 2,
+                    stack_size: 1
+                },
+                GameMove::ColumnToOtherColumn {
+                    from_column_index: 4,
+                    to_column_index: 2,
+                    stack_size: 1
+                },
+                GameMove::ColumnToOtherColumn {
+                    from_column_index: 5,
+                    to_column_index: 2,
+                    stack_size: 1
+                },
+                GameMove::ColumnToOtherColumn {
+                    from_column_index: 6,
+                    to_column_index: 2,
+                    stack_size: 1
+                },
+                GameMove::ColumnToOtherColumn {
+                    from_column_index: 7,
+                    to_column_index: 2,
+                    stack_size: 1
+                },
+                GameMove::TopLeftToColumn {
+                    top_left_index: 0,
+                    column_index: 2
+                },
+                GameMove::TopLeftToColumn {
+                    top_left_index: 2,
+                    column_index: 2
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let state = state();
+        assert_that!(
+            state.check_move(&GameMove::ToTopLeftStorage { column: 8 }),
+            err(eq(MoveError::ColumnOutOfRange { column: 8 }))
+        );
+        assert_that!(
+            state.check_move(&GameMove::TopLeftToColumn {
+                top_left_index: 3,
+                column_index: 0
+            }),
+            err(eq(MoveError::CellOutOfRange { cell: 3 }))
+        );
+        assert_that!(
+            state.check_move(&GameMove::ColumnToOtherColumn {
+                from_column_index: 0,
+                to_column_index: 9,
+                stack_size: 1
+            }),
+            err(eq(MoveError::ColumnOutOfRange { column: 9 }))
+        );
+    }
+
+    #[test]
+    fn test_foundation_errors() {
+        let state = state();
+        assert_that!(
+            state.check_move(&GameMove::ColumnToTopRightStorage { column: 2 }),
+            err(eq(MoveError::EmptyColumn { column: 2 }))
+        );
+        assert_that!(
+            state.check_move(&GameMove::ColumnToTopRightStorage { column: 0 }),
+            err(eq(MoveError::NotNextFoundationCard {
+                card: Card {
+                    suit: Red,
+                    value: Some(8)
+                }
+            }))
+        );
+        assert_that!(
+            state.check_move(&GameMove::TopLeftToTopRightStorage { top_left_index: 1 }),
+            err(eq(MoveError::NotNextFoundationCard {
+                card: Card {
+                    suit: FaceDown,
+                    value: None
+                }
+            }))
+        );
+        assert_that!(
+            state.check_move(&GameMove::ColumnToTopRightStorage { column: 4 }),
+            ok(eq(()))
+        );
+        assert_that!(
+            state.check_move(&GameMove::ColumnToTopRightStorage { column: 6 }),
+            ok(eq(()))
+        );
+    }
+
+    #[test]
+    fn test_cell_errors() {
+        let state = state();
+        assert_that!(
+            state.check_move(&GameMove::ToTopLeftStorage { column: 0 }),
+            err(eq(MoveError::NoFreeCell))
+        );
+        assert_that!(
+            state.check_move(&GameMove::TopLeftToColumn {
+                top_left_index: 1,
+                column_index: 2
+            }),
+            err(eq(MoveError::CollectedDragonsCannotMove { cell: 1 }))
+        );
+        assert_that!(
+            state.check_move(&GameMove::TopLeftToColumn {
+                top_left_index: 2,
+                column_index: 0
+            }),
+            err(eq(MoveError::IllegalTarget {
+                card: Card {
+                    suit: Green,
+                    value: Some(5)
+                },
+                target: Card {
+                    suit: Red,
+                    value: Some(8)
+                }
+            }))
+        );
+    }
+
+    #[test]
+    fn test_stack_errors() {
+        let state = state();
+        assert_that!(
+            state.check_move(&GameMove::ColumnToOtherColumn {
+                from_column_index: 1,
+                to_column_index: 1,
+                stack_size: 1
+            }),
+            err(eq(MoveError::SameColumn { column: 1 }))
+        );
+        assert_that!(
+            state.check_move(&GameMove::ColumnToOtherColumn {
+                from_column_index: 1,
+                to_column_index: 2,
+                stack_size: 4
+            }),
+            err(eq(MoveError::StackTooLarge {
+                column: 1,
+                stack_size: 4,
+                movable: 3
+            }))
+        );
+        assert_that!(
+            state.check_move(&GameMove::ColumnToOtherColumn {
+                from_column_index: 1,
+                to_column_index: 5,
+                stack_size: 1
+            }),
+            err(eq(MoveError::IllegalTarget {
+                card: Card {
+                    suit: Black,
+                    value: Some(4)
+                },
+                target: Card {
+                    suit: Green,
+                    value: None
+                }
+            }))
+        );
+        assert_that!(
+            state.check_move(&GameMove::ColumnToOtherColumn {
+                from_column_index: 1,
+                to_column_index: 2,
+                stack_size: 3
+            }),
+            ok(eq(()))
+        );
+    }
+
+    #[test]
+    fn test_dragon_errors() {
+        let state = state();
+        assert_that!(
+            state.check_move(&GameMove::CollectDragons { suit: Special }),
+            err(eq(MoveError::NotADragonSuit { suit: Special }))
+        );
+        assert_that!(
+            state.check_move(&GameMove::CollectDragons { suit: Green }),
+            err(eq(MoveError::NoFreeCell))
+        );
+        assert_that!(
+            state.check_move(&GameMove::CollectDragons { suit: Red }),
+            err(eq(MoveError::DragonsNotFree { suit: Red, free: 2 }))
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut state = state();
+        let before = state.clone();
+
+        assert_that!(
+            state.apply(&GameMove::ToTopLeftStorage { column: 0 }),
+            err(eq(MoveError::NoFreeCell))
+        );
+        assert_that!(state.columns.clone(), eq(before.columns.clone()));
+        assert_that!(
+            state.top_left_storage.clone(),
+            eq(before.top_left_storage.clone())
+        );
+
+        assert_that!(
+            state.apply(&GameMove::ColumnToTopRightStorage { column: 4 }),
+            ok(anything())
+        );
+        assert_that!(state.columns[4].is_empty(), eq(true));
+        assert_that!(state.top_right_storage, eq([1, 2, 3, 1]));
+    }
+}
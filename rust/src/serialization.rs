@@ -0,0 +1,478 @@
+// Serialize and Deserialize implementations, enabled by the serde feature.
+//
+// The serialized form is a documented schema that is kept separate from the
+// in-memory representation, so that files written by older versions keep
+// loading after the internals change. All of the conversions go through the
+// private *V1 types below, which must only ever be extended in a backwards
+// compatible way. Anything else needs a new schema version.
+//
+// Schema version 1, shown as JSON:
+//
+//   Card: a string in the text notation, see notation.rs
+//     "R5", "GD" (green dragon), "F" (flower), "XX" (collected dragons)
+//
+//   Suit: one of "special", "red", "green", "black", "face_down"
+//
+//   GameState:
+//     {
+//       "version": 1,
+//       "cells": ["RD", "XX"],
+//       "foundation": { "flower": 0, "red": 3, "green": 0, "black": 2 },
+//       "columns": [["BD", "RD", "B7"], [], ...]
+//     }
+//
+//     cells holds at most 3 cards, columns always holds exactly 8 columns,
+//     each listed from the bottom card to the top card.
+//
+//   GameMove: an object with a "type" field
+//     { "type": "start" }
+//     { "type": "column_to_foundation", "column": 0 }
+//     { "type": "cell_to_foundation", "cell": 0 }
+//     { "type": "collect_dragons", "suit": "red" }
+//     { "type": "column_to_column", "from_column": 0, "to_column": 1,
+//       "stack_size": 2 }
+//     { "type": "column_to_cell", "column": 0 }
+//     { "type": "cell_to_column", "cell": 0, "column": 1 }
+//
+//   The solution returned by Game::play is a list of [GameState, GameMove]
+//   pairs.
+//
+// Deserializing a GameState with a version newer than SCHEMA_VERSION fails,
+// rather than silently dropping information. Only the structure is checked,
+// so partial positions such as test fixtures load too, see
+// GameState::validate for checking that a position can come up in the game.
+
+use crate::card::*;
+use crate::game::GameMove;
+use crate::game_state::GameState;
+use crate::notation::{card_token, parse_card};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+impl Serialize for Card {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&card_token(self))
+    }
+}
+
+impl<'de> Deserialize<'de> for Card {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let token = String::deserialize(deserializer)?;
+        parse_card(&token).ok_or_else(|| {
+            D::Error::custom(format!(
+                "invalid card `{}`, expected a card such as R5, GD, F or XX",
+                token
+            ))
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SuitV1 {
+    Special,
+    Red,
+    Green,
+    Black,
+    FaceDown,
+}
+
+impl From<Suit> for SuitV1 {
+    fn from(suit: Suit) -> Self {
+        match suit {
+            Suit::Special => SuitV1::Special,
+            Suit::Red => SuitV1::Red,
+            Suit::Green => SuitV1::Green,
+            Suit::Black => SuitV1::Black,
+            Suit::FaceDown => SuitV1::FaceDown,
+        }
+    }
+}
+
+impl From<SuitV1> for Suit {
+    fn from(suit: SuitV1) -> Self {
+        match suit {
+            SuitV1::Special => Suit::Special,
+            SuitV1::Red => Suit::Red,
+            SuitV1::Green => Suit::Green,
+            SuitV1::Black => Suit::Black,
+            SuitV1::FaceDown => Suit::FaceDown,
+        }
+    }
+}
+
+impl Serialize for Suit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SuitV1::from(*self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Suit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        SuitV1::deserialize(deserializer).map(Suit::from)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct FoundationV1 {
+    flower: u8,
+    red: u8,
+    green: u8,
+    black: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GameStateV1 {
+    version: u32,
+    cells: Vec<Card>,
+    foundation: FoundationV1,
+    columns: [Vec<Card>; 8],
+}
+
+impl Serialize for GameState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        GameStateV1 {
+            version: SCHEMA_VERSION,
+            cells: self.top_left_storage.clone(),
+            foundation: FoundationV1 {
+                flower: self.top_right_storage[Suit::Special as usize],
+                red: self.top_right_storage[Suit::Red as usize],
+                green: self.top_right_storage[Suit::Green as usize],
+                black: self.top_right_storage[Suit::Black as usize],
+            },
+            columns: self.columns.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for GameState {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = GameStateV1::deserialize(deserializer)?;
+
+        if state.version > SCHEMA_VERSION {
+            return Err(D::Error::custom(format!(
+                "unsupported schema version {}, the newest supported version is {}",
+                state.version, SCHEMA_VERSION
+            )));
+        }
+
+        if state.cells.len() > 3 {
+            return Err(D::Error::invalid_length(
+                state.cells.len(),
+                &"at most 3 cells",
+            ));
+        }
+
+        let foundation = state.foundation;
+        let mut top_right_storage = [0; 4];
+        top_right_storage[Suit::Special as usize] = foundation.flower;
+        top_right_storage[Suit::Red as usize] = foundation.red;
+        top_right_storage[Suit::Green as usize] = foundation.green;
+        top_right_storage[Suit::Black as usize] = foundation.black;
+
+        Ok(GameState {
+            top_left_storage: state.cells,
+            top_right_storage,
+            columns: state.columns,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum GameMoveV1 {
+    Start,
+    ColumnToFoundation {
+        column: usize,
+    },
+    CellToFoundation {
+        cell: usize,
+    },
+    CollectDragons {
+        suit: Suit,
+    },
+    ColumnToColumn {
+        from_column: usize,
+        to_column: usize,
+        stack_size: usize,
+    },
+    ColumnToCell {
+        column: usize,
+    },
+    CellToColumn {
+        cell: usize,
+        column: usize,
+    },
+}
+
+impl From<&GameMove> for GameMoveV1 {
+    fn from(game_move: &GameMove) -> Self {
+        match *game_move {
+            GameMove::Start => GameMoveV1::Start,
+            GameMove::ColumnToTopRightStorage { column } => {
+                GameMoveV1::ColumnToFoundation { column }
+            }
+            GameMove::TopLeftToTopRightStorage { top_left_index } => GameMoveV1::CellToFoundation {
+                cell: top_left_index,
+            },
+            GameMove::CollectDragons { suit } => GameMoveV1::CollectDragons { suit },
+            GameMove::ColumnToOtherColumn {
+                from_column_index,
+                to_column_index,
+                stack_size,
+            } => GameMoveV1::ColumnToColumn {
+                from_column: from_column_index,
+                to_column: to_column_index,
+                stack_size,
+            },
+            GameMove::ToTopLeftStorage { column } => GameMoveV1::ColumnToCell { column },
+            GameMove::TopLeftToColumn {
+                top_left_index,
+                column_index,
+            } => GameMoveV1::CellToColumn {
+                cell: top_left_index,
+                column: column_index,
+            },
+        }
+    }
+}
+
+impl From<GameMoveV1> for GameMove {
+    fn from(game_move: GameMoveV1) -> Self {
+        match game_move {
+            GameMoveV1::Start => GameMove::Start,
+            GameMoveV1::ColumnToFoundation { column } => {
+                GameMove::ColumnToTopRightStorage { column }
+            }
+            GameMoveV1::CellToFoundation { cell } => GameMove::TopLeftToTopRightStorage {
+                top_left_index: cell,
+            },
+            GameMoveV1::CollectDragons { suit } => GameMove::CollectDragons { suit },
+            GameMoveV1::ColumnToColumn {
+                from_column,
+                to_column,
+                stack_size,
+            } => GameMove::ColumnToOtherColumn {
+                from_column_index: from_column,
+                to_column_index: to_column,
+                stack_size,
+            },
+            GameMoveV1::ColumnToCell { column } => GameMove::ToTopLeftStorage { column },
+            GameMoveV1::CellToColumn { cell, column } => GameMove::TopLeftToColumn {
+                top_left_index: cell,
+                column_index: column,
+            },
+        }
+    }
+}
+
+impl Serialize for GameMove {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        GameMoveV1::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for GameMove {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        GameMoveV1::deserialize(deserializer).map(GameMove::from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::Game;
+    use googletest::assert_that;
+    use googletest::matchers::*;
+    use serde_json::json;
+
+    fn deal() -> GameState {
+        include_str!("../deals/deal0.txt").parse().unwrap()
+    }
+
+    #[test]
+    fn test_card_schema() {
+        let test_data = [
+            (
+                Card {
+                    suit: Suit::Red,
+                    value: Some(5),
+                },
+                "R5",
+            ),
+            (
+                Card {
+                    suit: Suit::Green,
+                    value: None,
+                },
+                "GD",
+            ),
+            (
+                Card {
+                    suit: Suit::Special,
+                    value: Some(1),
+                },
+                "F",
+            ),
+            (
+                Card {
+                    suit: Suit::FaceDown,
+                    value: None,
+                },
+                "XX",
+            ),
+        ];
+
+        for (card, token) in test_data {
+            assert_that!(serde_json::to_value(card).unwrap(), eq(json!(token)));
+            assert_that!(
+                serde_json::from_value::<Card>(json!(token)).unwrap(),
+                eq(card)
+            );
+        }
+
+        assert_that!(
+            serde_json::from_value::<Card>(json!("R0")).is_err(),
+            eq(true)
+        );
+    }
+
+    #[test]
+    fn test_game_state_schema() {
+        let mut state = deal();
+        state.columns[0].pop();
+        state.top_left_storage.push(Card {
+            suit: Suit::Black,
+            value: Some(6),
+        });
+
+        let value = serde_json::to_value(&state).unwrap();
+        assert_that!(value["version"], eq(json!(1)));
+        assert_that!(value["cells"], eq(json!(["B6"])));
+        assert_that!(
+            value["foundation"],
+            eq(json!({"flower": 0, "red": 0, "green": 0, "black": 0}))
+        );
+        assert_that!(value["columns"][0], eq(json!(["BD", "RD", "B7", "R7"])));
+
+        let parsed: GameState = serde_json::from_value(value).unwrap();
+        assert_that!(parsed.top_left_storage, eq(state.top_left_storage));
+        assert_that!(parsed.top_right_storage, eq(state.top_right_storage));
+        assert_that!(parsed.columns, eq(state.columns));
+    }
+
+    #[test]
+    fn test_game_state_rejects_invalid_documents() {
+        let mut value = serde_json::to_value(deal()).unwrap();
+        value["version"] = json!(SCHEMA_VERSION + 1);
+        assert_that!(
+            serde_json::from_value::<GameState>(value).is_err(),
+            eq(true)
+        );
+
+        let mut value = serde_json::to_value(deal()).unwrap();
+        value["cells"] = json!(["RD", "GD", "BD", "R1"]);
+        assert_that!(
+            serde_json::from_value::<GameState>(value).is_err(),
+            eq(true)
+        );
+
+        let mut value = serde_json::to_value(deal()).unwrap();
+        value["columns"].as_array_mut().unwrap().pop();
+        assert_that!(
+            serde_json::from_value::<GameState>(value).is_err(),
+            eq(true)
+        );
+    }
+
+    #[test]
+    fn test_partial_position_round_trip() {
+        let state = GameState::parse_unchecked(
+            "1: R1
+2:
+3:
+4:
+5:
+6:
+7:
+8: GD GD
+",
+        )
+        .unwrap();
+        assert_that!(state.validate(), err(anything()));
+
+        let parsed: GameState =
+            serde_json::from_value(serde_json::to_value(&state).unwrap()).unwrap();
+        assert_that!(parsed.top_left_storage, eq(state.top_left_storage));
+        assert_that!(parsed.top_right_storage, eq(state.top_right_storage));
+        assert_that!(parsed.columns, eq(state.columns));
+    }
+
+    #[test]
+    fn test_game_move_schema() {
+        let test_data = [
+            (GameMove::Start, json!({"type": "start"})),
+            (
+                GameMove::ColumnToTopRightStorage { column: 3 },
+                json!({"type": "column_to_foundation", "column": 3}),
+            ),
+            (
+                GameMove::TopLeftToTopRightStorage { top_left_index: 1 },
+                json!({"type": "cell_to_foundation", "cell": 1}),
+            ),
+            (
+                GameMove::CollectDragons { suit: Suit::Green },
+                json!({"type": "collect_dragons", "suit": "green"}),
+            ),
+            (
+                GameMove::ColumnToOtherColumn {
+                    from_column_index: 0,
+                    to_column_index: 7,
+                    stack_size: 2,
+                },
+                json!({
+                    "type": "column_to_column",
+                    "from_column": 0,
+                    "to_column": 7,
+                    "stack_size": 2
+                }),
+            ),
+            (
+                GameMove::ToTopLeftStorage { column: 5 },
+                json!({"type": "column_to_cell", "column": 5}),
+            ),
+            (
+                GameMove::TopLeftToColumn {
+                    top_left_index: 2,
+                    column_index: 4,
+                },
+                json!({"type": "cell_to_column", "cell": 2, "column": 4}),
+            ),
+        ];
+
+        for (game_move, value) in test_data {
+            assert_that!(serde_json::to_value(&game_move).unwrap(), eq(value.clone()));
+            assert_that!(
+                serde_json::from_value::<GameMove>(value).unwrap(),
+                eq(game_move)
+            );
+        }
+    }
+
+    #[test]
+    fn test_solution_round_trip() {
+        let solution = Game::new().play(deal()).unwrap();
+
+        let text = serde_json::to_string(&solution).unwrap();
+        let parsed: Vec<(GameState, GameMove)> = serde_json::from_str(&text).unwrap();
+
+        assert_that!(parsed.len(), eq(solution.len()));
+        for ((parsed_state, parsed_move), (state, game_move)) in parsed.iter().zip(&solution) {
+            assert_that!(&parsed_state.columns, eq(&state.columns));
+            assert_that!(parsed_move, eq(game_move));
+        }
+    }
+}
@@ -0,0 +1,225 @@
+// A game being played by a person, for interactive front ends. Moves are
+// checked against the rules, every move can be undone and redone, and the
+// solver is on hand for hints or to finish the game.
+
+use crate::automatic::AutomaticMoves;
+use crate::game::{Game, GameMove};
+use crate::game_state::GameState;
+use crate::hint::Hints;
+use crate::limits::Limits;
+use crate::rules::MoveError;
+use crate::undo::Undo;
+
+pub struct Session {
+    state: GameState,
+    automatic_moves: AutomaticMoves,
+    // Each move made, followed by the moves the game made by itself after it.
+    // The last move made is at the end.
+    history: Vec<Vec<Undo>>,
+    // The moves undone since the last new move, the last one undone is at the
+    // end
+    redo: Vec<GameMove>,
+    hints: Hints,
+}
+
+impl Session {
+    // With emulated automatic moves, the game makes its moves on the deal
+    // straight away. Those can't be undone.
+    pub fn new(mut state: GameState, automatic_moves: AutomaticMoves) -> Session {
+        if automatic_moves == AutomaticMoves::Emulated {
+            state.apply_automatic_moves();
+        }
+
+        Session {
+            state,
+            automatic_moves,
+            history: vec![],
+            redo: vec![],
+            hints: Hints::new(move || Game::new().with_automatic_moves(automatic_moves)),
+        }
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    // The number of moves made, not counting undone moves or the ones the
+    // game made by itself
+    pub fn move_count(&self) -> usize {
+        self.history.len()
+    }
+
+    // Makes the move if it is legal. Returns the moves the game then made by
+    // itself.
+    pub fn play(&mut self, game_move: &GameMove) -> Result<Vec<GameMove>, MoveError> {
+        let automatic = self.make_move(game_move)?;
+        self.redo.clear();
+        Ok(automatic)
+    }
+
+    // Takes back the last move, along with the moves the game made after it.
+    // Returns the move taken back, or None at the start of the game.
+    pub fn undo(&mut self) -> Option<GameMove> {
+        let records = self.history.pop()?;
+        for record in records.iter().rev() {
+            self.state.undo(record);
+        }
+
+        let game_move = records[0].game_move();
+        self.redo.push(game_move.clone());
+        Some(game_move)
+    }
+
+    // Makes the last move taken back again. Returns the move, and the moves
+    // the game then made by itself.
+    pub fn redo(&mut self) -> Option<(GameMove, Vec<GameMove>)> {
+        let game_move = self.redo.pop()?;
+        let automatic = self
+            .make_move(&game_move)
+            .expect("Undo restores the exact position the move was made in");
+        Some((game_move, automatic))
+    }
+
+    // See Hints::hint
+    pub fn hint(&mut self, limits: Limits) -> Option<GameMove> {
+        self.hints.hint(&self.state, limits)
+    }
+
+    // Whether a hint proved there is no solution from the current position
+    pub fn is_lost(&self) -> bool {
+        self.hints.is_lost(&self.state)
+    }
+
+    // Plays a solution from the current position to the end. Each move can
+    // be undone like any other. Returns the moves made, or None if no solution
+    // was found, in which case no move is made.
+    pub fn finish(&mut self, limits: Limits) -> Option<Vec<GameMove>> {
+        if self.state.is_solved() {
+            return Some(vec![]);
+        }
+
+        let mut moves = vec![self.hint(limits)?];
+        loop {
+            self.play(moves.last().unwrap())
+                .expect("Hints are legal moves");
+            if self.state.is_solved() {
+                return Some(moves);
+            }
+            // Following the hint, so the rest of the solution is already known
+            moves.push(self.hint(limits).expect("The solution continues"));
+        }
+    }
+
+    fn make_move(&mut self, game_move: &GameMove) -> Result<Vec<GameMove>, MoveError> {
+        let mut records = vec![self.state.apply(game_move)?];
+        if self.automatic_moves == AutomaticMoves::Emulated {
+            records.extend(self.state.apply_automatic_moves());
+        }
+
+        let automatic = records[1..]
+            .iter()
+            .map(|record| record.game_move())
+            .collect();
+        self.history.push(records);
+        Ok(automatic)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::deal::deal;
+    use crate::verify::verify_moves;
+    use googletest::assert_that;
+    use googletest::matchers::*;
+
+    fn start() -> GameState {
+        include_str!("../deals/deal0.txt").parse().unwrap()
+    }
+
+    fn notation(state: &GameState) -> String {
+        state.notation().to_string()
+    }
+
+    #[test]
+    fn test_illegal_move() {
+        let mut session = Session::new(start(), AutomaticMoves::Emulated);
+        let before = notation(session.state());
+
+        assert_that!(
+            session.play(&GameMove::TopLeftToColumn {
+                top_left_index: 0,
+                column_index: 0,
+            }),
+            err(anything())
+        );
+        assert_that!(notation(session.state()), eq(before));
+        assert_that!(session.move_count(), eq(0));
+        assert_that!(session.undo(), none());
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut session = Session::new(start(), AutomaticMoves::Emulated);
+        let mut positions = vec![notation(session.state())];
+        let mut moves = vec![];
+
+        for _ in 0..10 {
+            let game_move = session.state().legal_moves()[0].clone();
+            session.play(&game_move).unwrap();
+            positions.push(notation(session.state()));
+            moves.push(game_move);
+        }
+
+        for i in (0..10).rev() {
+            assert_that!(session.undo(), some(eq(moves[i].clone())));
+            assert_that!(notation(session.state()), eq(positions[i].clone()));
+        }
+        assert_that!(session.undo(), none());
+
+        for i in 0..10 {
+            let (game_move, _) = session.redo().unwrap();
+            assert_that!(game_move, eq(moves[i].clone()));
+            assert_that!(notation(session.state()), eq(positions[i + 1].clone()));
+        }
+        assert_that!(session.redo(), none());
+
+        // A new move replaces the moves that could be redone
+        session.undo();
+        session.undo();
+        let game_move = session.state().legal_moves().pop().unwrap();
+        session.play(&game_move).unwrap();
+        assert_that!(session.redo(), none());
+        assert_that!(session.move_count(), eq(9));
+    }
+
+    #[test]
+    fn test_finish() {
+        let mut session = Session::new(start(), AutomaticMoves::Emulated);
+        let first = session.state().legal_moves()[0].clone();
+        session.play(&first).unwrap();
+        let position = session.state().clone();
+
+        let moves = session.finish(Limits::default()).unwrap();
+        assert_that!(session.state().is_solved(), eq(true));
+        assert_that!(session.move_count(), eq(moves.len() + 1));
+        assert_that!(
+            verify_moves(&position, &moves, AutomaticMoves::Emulated),
+            ok(anything())
+        );
+
+        // Finishing can be undone too
+        for _ in 0..moves.len() {
+            session.undo();
+        }
+        assert_that!(notation(session.state()), eq(notation(&position)));
+    }
+
+    #[test]
+    fn test_lost() {
+        let mut session = Session::new(deal(9), AutomaticMoves::Emulated);
+        assert_that!(session.finish(Limits::default()), none());
+        assert_that!(session.is_lost(), eq(true));
+        assert_that!(session.move_count(), eq(0));
+    }
+}
@@ -0,0 +1,253 @@
+// A compact form of a GameState, for storing the positions a search has
+// seen. A GameState keeps every column and the cells in their own Vec, so
+// storing one costs ten allocations, and hashing or comparing it sorts copies
+// of them. A key is a fixed 40 bytes, and is hashed and compared as plain
+// integers.
+//
+// Cards are packed into 6 bit codes, in this order:
+// - the foundation, one bit for the flower and 4 bits for each other suit
+// - the three cells, 0 for a free cell
+// - the columns from bottom to top, each ended by a 0
+//
+// The key is made from the canonical form of the position, so equal
+// positions have the same key, see GameState::canonical. Turning a key back
+// into a GameState gives the canonical form.
+
+use crate::card::*;
+use crate::game_state::GameState;
+
+const WORDS: usize = 5;
+const CODE_BITS: u32 = 6;
+const END: u64 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StateKey {
+    words: [u64; WORDS],
+}
+
+// 1 to 27 for the numbered cards, 28 to 30 for the dragons, then the flower,
+// and the face down card left in a cell by collecting dragons
+fn card_code(card: &Card) -> u64 {
+    match (card.suit, card.value) {
+        (Suit::Special, _) => 31,
+        (Suit::FaceDown, _) => 32,
+        (suit, None) => 28 + (suit as u64 - Suit::Red as u64),
+        (suit, Some(value)) => {
+            assert!(
+                (1..=9).contains(&value),
+                "{:?} is not a card in the game",
+                card
+            );
+            1 + (suit as u64 - Suit::Red as u64) * 9 + (value as u64 - 1)
+        }
+    }
+}
+
+fn code_card(code: u64) -> Card {
+    match code {
+        1..=27 => Card {
+            suit: Suit::try_from(Suit::Red as usize + (code as usize - 1) / 9).unwrap(),
+            value: Some((code as u8 - 1) % 9 + 1),
+        },
+        28..=30 => Card {
+            suit: Suit::try_from(Suit::Red as usize + code as usize - 28).unwrap(),
+            value: None,
+        },
+        // The flower card is hardcoded to have a value of 1, see
+        // GameState::can_move_column_to_top_right_storage
+        31 => Card {
+            suit: Suit::Special,
+            value: Some(1),
+        },
+        32 => Card {
+            suit: Suit::FaceDown,
+            value: None,
+        },
+        _ => panic!("{} is not a card code", code),
+    }
+}
+
+struct BitWriter {
+    words: [u64; WORDS],
+    position: u32,
+}
+
+impl BitWriter {
+    fn push(&mut self, value: u64, bits: u32) {
+        assert!(
+            self.position + bits <= WORDS as u32 * 64,
+            "The position has more cards than the game"
+        );
+        let word = (self.position / 64) as usize;
+        let offset = self.position % 64;
+        self.words[word] |= value << offset;
+        if offset + bits > 64 {
+            self.words[word + 1] |= value >> (64 - offset);
+        }
+        self.position += bits;
+    }
+}
+
+struct BitReader<'a> {
+    words: &'a [u64; WORDS],
+    position: u32,
+}
+
+impl BitReader<'_> {
+    fn next(&mut self, bits: u32) -> u64 {
+        let word = (self.position / 64) as usize;
+        let offset = self.position % 64;
+        let mut value = self.words[word] >> offset;
+        if offset + bits > 64 {
+            value |= self.words[word + 1] << (64 - offset);
+        }
+        self.position += bits;
+        value & ((1 << bits) - 1)
+    }
+}
+
+impl From<&GameState> for StateKey {
+    fn from(state: &GameState) -> Self {
+        let mut writer = BitWriter {
+            words: [0; WORDS],
+            position: 0,
+        };
+
+        writer.push(state.top_right_storage[Suit::Special as usize] as u64, 1);
+        for suit in [Suit::Red, Suit::Green, Suit::Black] {
+            writer.push(state.top_right_storage[suit as usize] as u64, 4);
+        }
+
+        let (cells, columns) = state.canonical_order();
+        assert!(cells.len() <= 3);
+        for cell in 0..3 {
+            writer.push(cells.get(cell).map_or(END, card_code), CODE_BITS);
+        }
+
+        for column in columns {
+            for card in column {
+                writer.push(card_code(card), CODE_BITS);
+            }
+            writer.push(END, CODE_BITS);
+        }
+
+        StateKey {
+            words: writer.words,
+        }
+    }
+}
+
+impl From<&StateKey> for GameState {
+    fn from(key: &StateKey) -> Self {
+        let mut reader = BitReader {
+            words: &key.words,
+            position: 0,
+        };
+
+        let mut top_right_storage = [0; 4];
+        top_right_storage[Suit::Special as usize] = reader.next(1) as u8;
+        for suit in [Suit::Red, Suit::Green, Suit::Black] {
+            top_right_storage[suit as usize] = reader.next(4) as u8;
+        }
+
+        let top_left_storage = (0..3)
+            .map(|_| reader.next(CODE_BITS))
+            .filter(|code| *code != END)
+            .map(code_card)
+            .collect();
+
+        let columns = std::array::from_fn(|_| {
+            let mut column = vec![];
+            loop {
+                match reader.next(CODE_BITS) {
+                    END => break column,
+                    code => column.push(code_card(code)),
+                }
+            }
+        });
+
+        GameState {
+            top_left_storage,
+            top_right_storage,
+            columns,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::deal::{deal, random_position};
+    use googletest::assert_that;
+    use googletest::matchers::*;
+
+    fn notation(state: &GameState) -> String {
+        state.notation().to_string()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for seed in 0..30 {
+            for state in [deal(seed), random_position(seed, 60)] {
+                let key = StateKey::from(&state);
+                let decoded = GameState::from(&key);
+                assert_that!(StateKey::from(&decoded), eq(key));
+
+                let canonical = state.canonical();
+                assert_that!(&decoded.top_left_storage, eq(&canonical.top_left_storage));
+                assert_that!(&decoded.columns, eq(&canonical.columns));
+                assert_that!(decoded.top_right_storage, eq(canonical.top_right_storage));
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_card() {
+        // Already in the sorted order
+        let state: GameState = GameState::parse_unchecked(
+            "cells: F GD XX\n\
+             foundation: F1 R9 G0 B5\n\
+             1:\n\
+             2:\n\
+             3:\n\
+             4:\n\
+             5:\n\
+             6:\n\
+             7: RD BD\n\
+             8: R1 R9 G1 G9 B1 B9\n",
+        )
+        .unwrap();
+
+        let decoded = GameState::from(&StateKey::from(&state));
+        assert_that!(notation(&decoded), eq(notation(&state)));
+    }
+
+    #[test]
+    fn test_permutations() {
+        let state: GameState = GameState::parse_unchecked(
+            "cells: RD B3\n\
+             1: G1 G2\n\
+             2:\n\
+             3: RD\n\
+             4: RD G3\n\
+             5:\n\
+             6:\n\
+             7:\n\
+             8:\n",
+        )
+        .unwrap();
+
+        let mut permuted = state.clone();
+        permuted.top_left_storage.reverse();
+        permuted.columns.reverse();
+        assert_that!(StateKey::from(&permuted), eq(StateKey::from(&state)));
+
+        let mut moved = state.clone();
+        moved.columns[3].pop();
+        moved.columns[0].push(Card {
+            suit: Suit::Green,
+            value: Some(3),
+        });
+        assert_that!(StateKey::from(&moved), not(eq(StateKey::from(&state))));
+    }
+}
@@ -0,0 +1,44 @@
+// Counters collected during a search, so heuristics and data structure
+// changes can be compared on more than the solution length.
+
+use std::fmt;
+use std::fmt::Formatter;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    pub expanded_nodes: usize,
+    // Every child of an expanded node, including the duplicates
+    pub generated_nodes: usize,
+    // Children that were already in the closed set, and were dropped
+    pub duplicates: usize,
+    pub peak_open_states: usize,
+    // Moves the game made by itself after the generated moves, see
+    // apply_automatic_moves
+    pub automatic_moves: usize,
+    // The most moves on any path searched
+    pub max_depth: usize,
+    // Finding the legal moves and making them
+    pub move_generation_time: Duration,
+    // Looking up and inserting states in the closed set
+    pub hashing_time: Duration,
+    pub total_time: Duration,
+}
+
+impl fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "expanded nodes: {}", self.expanded_nodes)?;
+        writeln!(f, "generated nodes: {}", self.generated_nodes)?;
+        writeln!(f, "duplicates: {}", self.duplicates)?;
+        writeln!(f, "peak open states: {}", self.peak_open_states)?;
+        writeln!(f, "automatic moves: {}", self.automatic_moves)?;
+        writeln!(f, "max depth: {}", self.max_depth)?;
+        writeln!(
+            f,
+            "move generation time: {:.3}s",
+            self.move_generation_time.as_secs_f64()
+        )?;
+        writeln!(f, "hashing time: {:.3}s", self.hashing_time.as_secs_f64())?;
+        writeln!(f, "total time: {:.3}s", self.total_time.as_secs_f64())
+    }
+}
@@ -0,0 +1,294 @@
+// Make/unmake support. Applying a move returns an Undo record holding
+// everything needed to restore the exact previous state in place, so a search
+// or an interactive front end can step backwards without keeping a copy of
+// every state.
+
+use crate::card::*;
+use crate::game::GameMove;
+use crate::game_state::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Undo {
+    Start,
+    ColumnToTopRightStorage {
+        column: usize,
+        card: Card,
+        previous_level: u8,
+    },
+    TopLeftToTopRightStorage {
+        top_left_index: usize,
+        card: Card,
+        previous_level: u8,
+    },
+    CollectDragons {
+        suit: Suit,
+        // Where the dragons were collected from, the cells are listed in
+        // ascending order
+        columns: Vec<usize>,
+        top_left_indices: Vec<usize>,
+        // The cell the collected dragons ended up in
+        top_left_index: usize,
+    },
+    ColumnToOtherColumn {
+        from_column_index: usize,
+        to_column_index: usize,
+        stack_size: usize,
+    },
+    ToTopLeftStorage {
+        column: usize,
+        top_left_index: usize,
+    },
+    TopLeftToColumn {
+        top_left_index: usize,
+        column_index: usize,
+    },
+}
+
+impl Undo {
+    // The move this record undoes
+    pub fn game_move(&self) -> GameMove {
+        match *self {
+            Undo::Start => GameMove::Start,
+            Undo::ColumnToTopRightStorage { column, .. } => {
+                GameMove::ColumnToTopRightStorage { column }
+            }
+            Undo::TopLeftToTopRightStorage { top_left_index, .. } => {
+                GameMove::TopLeftToTopRightStorage { top_left_index }
+            }
+            Undo::CollectDragons { suit, .. } => GameMove::CollectDragons { suit },
+            Undo::ColumnToOtherColumn {
+                from_column_index,
+                to_column_index,
+                stack_size,
+            } => GameMove::ColumnToOtherColumn {
+                from_column_index,
+                to_column_index,
+                stack_size,
+            },
+            Undo::ToTopLeftStorage { column, .. } => GameMove::ToTopLeftStorage { column },
+            Undo::TopLeftToColumn {
+                top_left_index,
+                column_index,
+            } => GameMove::TopLeftToColumn {
+                top_left_index,
+                column_index,
+            },
+        }
+    }
+}
+
+impl GameState {
+    // Must be called before the move is made
+    pub(crate) fn undo_record(&self, game_move: &GameMove) -> Undo {
+        match *game_move {
+            GameMove::Start => Undo::Start,
+            GameMove::ColumnToTopRightStorage { column } => {
+                let card = *self.columns[column].last().unwrap();
+                Undo::ColumnToTopRightStorage {
+                    column,
+                    card,
+                    previous_level: self.top_right_storage[card.suit as usize],
+                }
+            }
+            GameMove::TopLeftToTopRightStorage { top_left_index } => {
+                let card = self.top_left_storage[top_left_index];
+                Undo::TopLeftToTopRightStorage {
+                    top_left_index,
+                    card,
+                    previous_level: self.top_right_storage[card.suit as usize],
+                }
+            }
+            GameMove::CollectDragons { suit } => {
+                let columns: Vec<usize> = (0..8)
+                    .filter(|column| {
+                        self.columns[*column]
+                            .last()
+                            .is_some_and(|card| card.is_dragon_with_suit(suit))
+                    })
+                    .collect();
+                let top_left_indices: Vec<usize> = (0..self.top_left_storage.len())
+                    .filter(|i| self.top_left_storage[*i].is_dragon_with_suit(suit))
+                    .collect();
+
+                Undo::CollectDragons {
+                    suit,
+                    columns,
+                    top_left_index: self.top_left_storage.len() - top_left_indices.len(),
+                    top_left_indices,
+                }
+            }
+            GameMove::ColumnToOtherColumn {
+                from_column_index,
+                to_column_index,
+                stack_size,
+            } => Undo::ColumnToOtherColumn {
+                from_column_index,
+                to_column_index,
+                stack_size,
+            },
+            GameMove::ToTopLeftStorage { column } => Undo::ToTopLeftStorage {
+                column,
+                top_left_index: self.top_left_storage.len(),
+            },
+            GameMove::TopLeftToColumn {
+                top_left_index,
+                column_index,
+            } => Undo::TopLeftToColumn {
+                top_left_index,
+                column_index,
+            },
+        }
+    }
+
+    // Restores the state from before the move. Records must be undone in the
+    // reverse order they were created in.
+    pub fn undo(&mut self, undo: &Undo) {
+        match *undo {
+            Undo::Start => {}
+            Undo::ColumnToTopRightStorage {
+                column,
+                card,
+                previous_level,
+            } => {
+                self.top_right_storage[card.suit as usize] = previous_level;
+                self.columns[column].push(card);
+            }
+            Undo::TopLeftToTopRightStorage {
+                top_left_index,
+                card,
+                previous_level,
+            } => {
+                self.top_right_storage[card.suit as usize] = previous_level;
+                self.top_left_storage.insert(top_left_index, card);
+            }
+            Undo::CollectDragons {
+                suit,
+                ref columns,
+                ref top_left_indices,
+                top_left_index,
+            } => {
+                let dragon = Card { suit, value: None };
+                self.top_left_storage.remove(top_left_index);
+                for i in top_left_indices {
+                    self.top_left_storage.insert(*i, dragon);
+                }
+                for column in columns {
+                    self.columns[*column].push(dragon);
+                }
+            }
+            Undo::ColumnToOtherColumn {
+                from_column_index,
+                to_column_index,
+                stack_size,
+            } => self.move_column_to_other_column(MoveColumnParameters {
+                from_column_index: to_column_index,
+                to_column_index: from_column_index,
+                stack_size,
+            }),
+            Undo::ToTopLeftStorage {
+                column,
+                top_left_index,
+            } => {
+                let card = self.top_left_storage.remove(top_left_index);
+                self.columns[column].push(card);
+            }
+            Undo::TopLeftToColumn {
+                top_left_index,
+                column_index,
+            } => {
+                let card = self.columns[column_index].pop().unwrap();
+                self.top_left_storage.insert(top_left_index, card);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::deal::{deal, random_position, Rng};
+    use googletest::assert_that;
+    use googletest::matchers::*;
+
+    // GameState equality merges symmetric positions, so compare the fields
+    // directly
+    fn assert_identical(a: &GameState, b: &GameState) {
+        assert_that!(&a.columns, eq(&b.columns));
+        assert_that!(&a.top_left_storage, eq(&b.top_left_storage));
+        assert_that!(a.top_right_storage, eq(b.top_right_storage));
+    }
+
+    #[test]
+    fn test_undo_every_legal_move() {
+        for seed in 0..30 {
+            for move_count in [0, 15, 50, 100] {
+                let state = random_position(seed, move_count);
+                for game_move in state.legal_moves() {
+                    let mut copy = state.clone();
+                    let undo = copy.apply(&game_move).unwrap();
+                    assert_that!(undo.game_move(), eq(game_move.clone()));
+
+                    copy.undo(&undo);
+                    assert_identical(&copy, &state);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_undo_collect_dragons() {
+        let state: GameState = GameState::parse_unchecked(
+            "cells: GD R5 GD\n\
+             foundation: F1 R4 G9 B9\n\
+             1: GD\n\
+             2: R6\n\
+             3: GD\n\
+             4:\n\
+             5:\n\
+             6:\n\
+             7:\n\
+             8:\n",
+        )
+        .unwrap();
+
+        let mut copy = state.clone();
+        let undo = copy
+            .apply(&GameMove::CollectDragons { suit: Suit::Green })
+            .unwrap();
+        assert_that!(
+            undo,
+            eq(Undo::CollectDragons {
+                suit: Suit::Green,
+                columns: vec![0, 2],
+                top_left_indices: vec![0, 2],
+                top_left_index: 1,
+            })
+        );
+
+        copy.undo(&undo);
+        assert_identical(&copy, &state);
+    }
+
+    #[test]
+    fn test_undo_sequence() {
+        let start = deal(5);
+        let mut state = start.clone();
+        let mut rng = Rng::new(5);
+        let mut history = vec![];
+
+        for _ in 0..200 {
+            let moves = state.legal_moves();
+            if moves.is_empty() {
+                break;
+            }
+            let game_move = &moves[rng.below(moves.len())];
+            history.push((state.clone(), state.apply(game_move).unwrap()));
+        }
+
+        while let Some((previous, undo)) = history.pop() {
+            state.undo(&undo);
+            assert_identical(&state, &previous);
+        }
+        assert_identical(&state, &start);
+    }
+}
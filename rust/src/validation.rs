@@ -0,0 +1,459 @@
+// Checks that a game state could actually come up in the game. The solver
+// assumes this is the case, and will either panic or search nonsense
+// positions otherwise.
+
+use crate::card::*;
+use crate::game_state::GameState;
+use crate::notation::card_token;
+use std::fmt;
+use std::fmt::Formatter;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    // Card inventory
+
+    // A card that does not exist in the deck, e.g. a red 10
+    InvalidCard {
+        card: Card,
+    },
+    // Collected dragons only ever go in the cells
+    FaceDownInColumn {
+        column: usize,
+    },
+    DuplicateCard {
+        card: Card,
+        count: usize,
+    },
+    // Not on the table and not in the foundation
+    MissingCard {
+        card: Card,
+    },
+    // On the table even though the foundation already contains it
+    CollectedCardInPlay {
+        card: Card,
+    },
+    // All four dragons of a suit are either in play or collected together
+    WrongDragonCount {
+        suit: Suit,
+        count: usize,
+    },
+
+    // Cell capacity
+    TooManyCells {
+        count: usize,
+    },
+
+    // Foundation consistency
+    InvalidFoundationLevel {
+        suit: Suit,
+        level: u8,
+    },
+
+    // Dragon collection consistency
+
+    // Each suit of dragons that is no longer in play takes up a cell
+    CollectedDragonMismatch {
+        face_down_cells: usize,
+        collected_suits: usize,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::InvalidCard { card } => write!(f, "{:?} is not a valid card", card),
+            Violation::FaceDownInColumn { column } => write!(
+                f,
+                "column {} contains collected dragons, they can only be in a cell",
+                column + 1
+            ),
+            Violation::DuplicateCard { card, count } => {
+                write!(f, "{} appears {} times", card_token(card), count)
+            }
+            Violation::MissingCard { card } => write!(
+                f,
+                "{} is missing, it is not in play or in the foundation",
+                card_token(card)
+            ),
+            Violation::CollectedCardInPlay { card } => write!(
+                f,
+                "{} is in play, but the foundation already contains it",
+                card_token(card)
+            ),
+            Violation::WrongDragonCount { suit, count } => write!(
+                f,
+                "{} {:?} dragons are in play, expected 4 or 0",
+                count, suit
+            ),
+            Violation::TooManyCells { count } => {
+                write!(f, "{} cells are in use, there are only 3", count)
+            }
+            Violation::InvalidFoundationLevel { suit, level } => {
+                write!(f, "the {:?} foundation is at invalid level {}", suit, level)
+            }
+            Violation::CollectedDragonMismatch {
+                face_down_cells,
+                collected_suits,
+            } => write!(
+                f,
+                "{} cells hold collected dragons, but {} suits of dragons are out of play",
+                face_down_cells, collected_suits
+            ),
+        }
+    }
+}
+
+fn max_foundation_level(suit: Suit) -> u8 {
+    if suit == Suit::Special {
+        1
+    } else {
+        9
+    }
+}
+
+fn is_valid_card(card: &Card) -> bool {
+    match (card.suit, card.value) {
+        (Suit::Special, value) => value == Some(1),
+        (Suit::FaceDown, value) => value.is_none(),
+        (_, None) => true,
+        (_, Some(value)) => (1..=9).contains(&value),
+    }
+}
+
+impl GameState {
+    // Returns every problem found, rather than stopping at the first one, so
+    // a hand typed deal can be fixed in one go
+    pub fn validate(&self) -> Result<(), Vec<Violation>> {
+        let mut violations = vec![];
+
+        if self.top_left_storage.len() > 3 {
+            violations.push(Violation::TooManyCells {
+                count: self.top_left_storage.len(),
+            });
+        }
+
+        for (suit, level) in self.top_right_storage.iter().enumerate() {
+            let suit = Suit::try_from(suit).unwrap();
+            if *level > max_foundation_level(suit) {
+                violations.push(Violation::InvalidFoundationLevel {
+                    suit,
+                    level: *level,
+                });
+            }
+        }
+
+        for (column_index, column) in self.columns.iter().enumerate() {
+            if column.iter().any(|card| card.suit == Suit::FaceDown) {
+                violations.push(Violation::FaceDownInColumn {
+                    column: column_index,
+                });
+            }
+        }
+
+        let mut cards: Vec<Card> = self
+            .columns
+            .iter()
+            .flatten()
+            .chain(&self.top_left_storage)
+            .copied()
+            .collect();
+        cards.sort();
+
+        // Face down cards are checked too, they must not have a value
+        for card in &cards {
+            if !is_valid_card(card) && !violations.contains(&Violation::InvalidCard { card: *card })
+            {
+                violations.push(Violation::InvalidCard { card: *card });
+            }
+        }
+
+        let cards_in_play: Vec<Card> = cards
+            .into_iter()
+            .filter(|card| card.suit != Suit::FaceDown)
+            .collect();
+
+        let count = |card: &Card| cards_in_play.iter().filter(|other| *other == card).count();
+
+        let flower = Card {
+            suit: Suit::Special,
+            value: Some(1),
+        };
+        violations.extend(check_card(
+            flower,
+            count(&flower),
+            self.top_right_storage[Suit::Special as usize] >= 1,
+        ));
+
+        let mut collected_suits = 0;
+        for suit in [Suit::Red, Suit::Green, Suit::Black] {
+            for value in 1..=9 {
+                let card = Card {
+                    suit,
+                    value: Some(value),
+                };
+                violations.extend(check_card(
+                    card,
+                    count(&card),
+                    self.top_right_storage[suit as usize] >= value,
+                ));
+            }
+
+            match count(&Card { suit, value: None }) {
+                0 => collected_suits += 1,
+                4 => {}
+                count => violations.push(Violation::WrongDragonCount { suit, count }),
+            }
+        }
+
+        let face_down_cells = self
+            .top_left_storage
+            .iter()
+            .filter(|card| card.suit == Suit::FaceDown)
+            .count();
+        if face_down_cells != collected_suits {
+            violations.push(Violation::CollectedDragonMismatch {
+                face_down_cells,
+                collected_suits,
+            });
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+fn check_card(card: Card, count: usize, collected: bool) -> Option<Violation> {
+    match (count, collected) {
+        (0, false) => Some(Violation::MissingCard { card }),
+        (1, false) | (0, true) => None,
+        (1, true) => Some(Violation::CollectedCardInPlay { card }),
+        (count, _) => Some(Violation::DuplicateCard { card, count }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::card::Suit::{Black, FaceDown, Green, Red, Special};
+    use crate::deal::deal;
+    use crate::game::Game;
+    use googletest::assert_that;
+    use googletest::matchers::*;
+
+    fn deal0() -> GameState {
+        include_str!("../deals/deal0.txt").parse().unwrap()
+    }
+
+    fn violations(state: &GameState) -> Vec<Violation> {
+        state.validate().err().unwrap_or_default()
+    }
+
+    #[test]
+    fn test_deals_are_valid() {
+        for seed in 0..20 {
+            assert_that!(deal(seed).validate(), ok(anything()));
+        }
+
+        assert_that!(deal0().validate(), ok(anything()));
+    }
+
+    #[test]
+    fn test_solution_states_are_valid() {
+        let state: GameState = include_str!("../deals/deal1.txt").parse().unwrap();
+        for (state, _) in Game::new().play(state).unwrap() {
+            assert_that!(state.validate(), ok(anything()));
+        }
+    }
+
+    #[test]
+    fn test_card_inventory() {
+        let mut state = deal0();
+        // B6
+        state.columns[0].pop();
+        // GD
+        state.columns[6].pop();
+        state.columns[1].push(Card {
+            suit: Red,
+            value: Some(10),
+        });
+        state.columns[2].push(Card {
+            suit: FaceDown,
+            value: None,
+        });
+
+        assert_that!(
+            violations(&state),
+            eq(vec![
+                Violation::FaceDownInColumn { column: 2 },
+                Violation::InvalidCard {
+                    card: Card {
+                        suit: Red,
+                        value: Some(10)
+                    }
+                },
+                Violation::WrongDragonCount {
+                    suit: Green,
+                    count: 3
+                },
+                Violation::MissingCard {
+                    card: Card {
+                        suit: Black,
+                        value: Some(6)
+                    }
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let mut state = deal0();
+        // R4 becomes a second copy of B1, and there are now five red dragons
+        state.columns[1].pop();
+        state.columns[1].push(Card {
+            suit: Black,
+            value: Some(1),
+        });
+        state.columns[2].push(Card {
+            suit: Red,
+            value: None,
+        });
+
+        assert_that!(
+            violations(&state),
+            eq(vec![
+                Violation::MissingCard {
+                    card: Card {
+                        suit: Red,
+                        value: Some(4)
+                    }
+                },
+                Violation::WrongDragonCount {
+                    suit: Red,
+                    count: 5
+                },
+                Violation::DuplicateCard {
+                    card: Card {
+                        suit: Black,
+                        value: Some(1)
+                    },
+                    count: 2
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_cell_capacity() {
+        let mut state = deal0();
+        for column in 0..4 {
+            let card = state.columns[column].pop().unwrap();
+            state.top_left_storage.push(card);
+        }
+
+        assert_that!(
+            violations(&state),
+            eq(vec![Violation::TooManyCells { count: 4 }])
+        );
+    }
+
+    #[test]
+    fn test_foundation_consistency() {
+        let mut state = GameState {
+            top_left_storage: vec![
+                Card {
+                    suit: FaceDown,
+                    value: None,
+                };
+                3
+            ],
+            top_right_storage: [1, 9, 9, 8],
+            columns: Default::default(),
+        };
+        state.columns[0].push(Card {
+            suit: Black,
+            value: Some(9),
+        });
+        assert_that!(state.validate(), ok(anything()));
+
+        state.top_right_storage = [2, 9, 10, 9];
+        assert_that!(
+            violations(&state),
+            eq(vec![
+                Violation::InvalidFoundationLevel {
+                    suit: Special,
+                    level: 2
+                },
+                Violation::InvalidFoundationLevel {
+                    suit: Green,
+                    level: 10
+                },
+                Violation::CollectedCardInPlay {
+                    card: Card {
+                        suit: Black,
+                        value: Some(9)
+                    }
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_face_down_card_with_a_value() {
+        let mut state = GameState {
+            top_left_storage: vec![
+                Card {
+                    suit: FaceDown,
+                    value: None,
+                };
+                3
+            ],
+            top_right_storage: [1, 9, 9, 9],
+            columns: Default::default(),
+        };
+        state.top_left_storage[1].value = Some(3);
+
+        assert_that!(
+            violations(&state),
+            eq(vec![Violation::InvalidCard {
+                card: Card {
+                    suit: FaceDown,
+                    value: Some(3)
+                }
+            }])
+        );
+    }
+
+    #[test]
+    fn test_dragon_collection_consistency() {
+        let mut state = deal0();
+        state.top_left_storage.push(Card {
+            suit: FaceDown,
+            value: None,
+        });
+
+        assert_that!(
+            violations(&state),
+            eq(vec![Violation::CollectedDragonMismatch {
+                face_down_cells: 1,
+                collected_suits: 0,
+            }])
+        );
+
+        let mut state = deal0();
+        for column in &mut state.columns {
+            column.retain(|card| !card.is_dragon_with_suit(Green));
+        }
+
+        assert_that!(
+            violations(&state),
+            eq(vec![Violation::CollectedDragonMismatch {
+                face_down_cells: 0,
+                collected_suits: 1,
+            }])
+        );
+    }
+}
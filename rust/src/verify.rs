@@ -0,0 +1,336 @@
+// Independent check of a solution, whether it came from Game::play, was
+// written by hand or was produced by the python solver. Every move is replayed
+// with full legality checking, rather than trusting the recorded states.
+//
+// Solutions from Game::play leave out the moves the game makes by itself, so
+// they have to be replayed with the same automatic moves setting the solver
+// used. Solutions that list every move to the foundation, like those from the
+// python solver, are replayed with AutomaticMoves::Manual.
+
+use crate::automatic::AutomaticMoves;
+use crate::game::GameMove;
+use crate::game_state::GameState;
+use crate::rules::MoveError;
+use crate::undo::Undo;
+use crate::validation::Violation;
+use std::fmt;
+use std::fmt::Formatter;
+use std::iter;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyErrorKind {
+    InvalidStart(Vec<Violation>),
+    // GameMove::Start is only allowed as the first step
+    MisplacedStart,
+    IllegalMove(MoveError),
+    // The recorded state does not match the result of replaying the moves
+    StateMismatch {
+        expected: Box<GameState>,
+        actual: Box<GameState>,
+    },
+    NotSolved,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyError {
+    // Index into the solution of the first bad step
+    pub step: usize,
+    pub game_move: Option<GameMove>,
+    pub kind: VerifyErrorKind,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "step {}", self.step)?;
+        if let Some(game_move) = &self.game_move {
+            write!(f, " ({})", game_move)?;
+        }
+        f.write_str(": ")?;
+
+        match &self.kind {
+            VerifyErrorKind::InvalidStart(violations) => {
+                f.write_str("the start position is invalid")?;
+                for violation in violations {
+                    write!(f, ", {}", violation)?;
+                }
+                Ok(())
+            }
+            VerifyErrorKind::MisplacedStart => {
+                f.write_str("start can only be the first move of a solution")
+            }
+            VerifyErrorKind::IllegalMove(error) => write!(f, "illegal move, {}", error),
+            VerifyErrorKind::StateMismatch { expected, actual } => write!(
+                f,
+                "the recorded state does not match the replayed state\n\
+                 recorded:\n{}replayed:\n{}",
+                expected.notation(),
+                actual.notation()
+            ),
+            VerifyErrorKind::NotSolved => f.write_str("the game is not solved after the last move"),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+// Compares every field exactly. GameState equality treats some symmetric
+// positions as equal, which is too lenient here.
+pub(crate) fn identical(a: &GameState, b: &GameState) -> bool {
+    a.columns == b.columns
+        && a.top_left_storage == b.top_left_storage
+        && a.top_right_storage == b.top_right_storage
+}
+
+// Verifies a sequence of moves from the start position. The first move may be
+// GameMove::Start, matching the solutions returned by Game::play.
+pub fn verify_moves(
+    start: &GameState,
+    moves: &[GameMove],
+    automatic_moves: AutomaticMoves,
+) -> Result<GameState, VerifyError> {
+    verify(start, moves.iter(), iter::repeat(None), automatic_moves)
+}
+
+// Verifies a solution as returned by Game::play, where each move is paired
+// with the state it results in
+pub fn verify_solution(
+    start: &GameState,
+    solution: &[(GameState, GameMove)],
+    automatic_moves: AutomaticMoves,
+) -> Result<GameState, VerifyError> {
+    verify(
+        start,
+        solution.iter().map(|(_, game_move)| game_move),
+        solution.iter().map(|(state, _)| Some(state)),
+        automatic_moves,
+    )
+}
+
+fn verify<'a>(
+    start: &GameState,
+    moves: impl Iterator<Item = &'a GameMove>,
+    mut expected_states: impl Iterator<Item = Option<&'a GameState>>,
+    automatic_moves: AutomaticMoves,
+) -> Result<GameState, VerifyError> {
+    let mut step_count = 0;
+    let (_, state) = replay_moves(start, moves, automatic_moves, |step| {
+        step_count += 1;
+        match expected_states.next().flatten() {
+            Some(expected) if !identical(expected, step.state) => {
+                Err(VerifyErrorKind::StateMismatch {
+                    expected: Box::new(expected.clone()),
+                    actual: Box::new(step.state.clone()),
+                })
+            }
+            _ => Ok(()),
+        }
+    })?;
+
+    // Legal moves from a valid position always lead to a valid position, so
+    // is_solved won't hit any of its assertions
+    if !state.is_solved() {
+        return Err(VerifyError {
+            step: step_count,
+            game_move: None,
+            kind: VerifyErrorKind::NotSolved,
+        });
+    }
+
+    Ok(state)
+}
+
+// A move made by replay_moves
+pub(crate) struct Step<'a> {
+    pub game_move: &'a GameMove,
+    // The position before the move
+    pub before: &'a GameState,
+    pub record: &'a Undo,
+    // The moves the game made by itself afterwards, in order
+    pub automatic: &'a [Undo],
+    // The position after all of them
+    pub state: &'a GameState,
+}
+
+// The replay behind both verify_* and Replay::new. Every move is checked, and
+// when automatic moves are emulated the game makes its own moves after the
+// deal and after each move. on_step sees each move in turn, including a
+// leading GameMove::Start, and can stop the replay with an error for that
+// move. Returns the position after the deal and the final position.
+pub(crate) fn replay_moves<'a>(
+    start: &GameState,
+    moves: impl Iterator<Item = &'a GameMove>,
+    automatic_moves: AutomaticMoves,
+    mut on_step: impl FnMut(Step<'_>) -> Result<(), VerifyErrorKind>,
+) -> Result<(GameState, GameState), VerifyError> {
+    if let Err(violations) = start.validate() {
+        return Err(VerifyError {
+            step: 0,
+            game_move: None,
+            kind: VerifyErrorKind::InvalidStart(violations),
+        });
+    }
+
+    let emulated = automatic_moves == AutomaticMoves::Emulated;
+    let mut state = start.clone();
+    if emulated {
+        state.apply_automatic_moves();
+    }
+    let dealt = state.clone();
+
+    for (step, game_move) in moves.enumerate() {
+        let error = |kind| VerifyError {
+            step,
+            game_move: Some(game_move.clone()),
+            kind,
+        };
+
+        if *game_move == GameMove::Start && step != 0 {
+            return Err(error(VerifyErrorKind::MisplacedStart));
+        }
+
+        let before = state.clone();
+        let record = state
+            .apply(game_move)
+            .map_err(|move_error| error(VerifyErrorKind::IllegalMove(move_error)))?;
+        let automatic = if emulated {
+            state.apply_automatic_moves()
+        } else {
+            vec![]
+        };
+
+        on_step(Step {
+            game_move,
+            before: &before,
+            record: &record,
+            automatic: &automatic,
+            state: &state,
+        })
+        .map_err(error)?;
+    }
+
+    Ok((dealt, state))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::card::*;
+    use crate::game::Game;
+    use googletest::assert_that;
+    use googletest::matchers::*;
+
+    fn deal() -> GameState {
+        include_str!("../deals/deal1.txt").parse().unwrap()
+    }
+
+    fn solution() -> Vec<(GameState, GameMove)> {
+        Game::new().play(deal()).unwrap()
+    }
+
+    fn kind(result: Result<GameState, VerifyError>) -> VerifyErrorKind {
+        result.unwrap_err().kind
+    }
+
+    #[test]
+    fn test_solver_solutions_verify() {
+        let solution = solution();
+        assert_that!(
+            verify_solution(&deal(), &solution, AutomaticMoves::Emulated),
+            ok(anything())
+        );
+
+        let moves: Vec<GameMove> = solution.into_iter().map(|(_, m)| m).collect();
+        assert_that!(
+            verify_moves(&deal(), &moves, AutomaticMoves::Emulated),
+            ok(anything())
+        );
+        // The start marker is optional
+        assert_that!(
+            verify_moves(&deal(), &moves[1..], AutomaticMoves::Emulated),
+            ok(anything())
+        );
+    }
+
+    #[test]
+    fn test_illegal_move() {
+        let mut solution = solution();
+        solution[11].1 = GameMove::ToTopLeftStorage { column: 9 };
+
+        let error = verify_solution(&deal(), &solution, AutomaticMoves::Emulated).unwrap_err();
+        assert_that!(error.step, eq(11));
+        assert_that!(
+            error.kind,
+            eq(VerifyErrorKind::IllegalMove(MoveError::ColumnOutOfRange {
+                column: 9
+            }))
+        );
+    }
+
+    #[test]
+    fn test_state_mismatch() {
+        let mut solution = solution();
+        solution[5].0.columns[0].push(Card {
+            suit: Suit::Red,
+            value: None,
+        });
+
+        let error = verify_solution(&deal(), &solution, AutomaticMoves::Emulated).unwrap_err();
+        assert_that!(error.step, eq(5));
+        assert_that!(
+            matches!(error.kind, VerifyErrorKind::StateMismatch { .. }),
+            eq(true)
+        );
+    }
+
+    #[test]
+    fn test_wrong_start_state() {
+        let mut solution = solution();
+        solution[0].0.columns.swap(0, 1);
+
+        let error = verify_solution(&deal(), &solution, AutomaticMoves::Emulated).unwrap_err();
+        assert_that!(error.step, eq(0));
+    }
+
+    #[test]
+    fn test_not_solved() {
+        let mut solution = solution();
+        let length = solution.len();
+        solution.pop();
+
+        let error = verify_solution(&deal(), &solution, AutomaticMoves::Emulated).unwrap_err();
+        assert_that!(error.step, eq(length - 1));
+        assert_that!(error.kind, eq(VerifyErrorKind::NotSolved));
+    }
+
+    #[test]
+    fn test_misplaced_start() {
+        let mut solution = solution();
+        solution[3].1 = GameMove::Start;
+
+        assert_that!(
+            kind(verify_solution(
+                &deal(),
+                &solution,
+                AutomaticMoves::Emulated
+            )),
+            eq(VerifyErrorKind::MisplacedStart)
+        );
+    }
+
+    #[test]
+    fn test_invalid_start() {
+        let mut start = deal();
+        start.columns[0].push(Card {
+            suit: Suit::Red,
+            value: Some(1),
+        });
+
+        assert_that!(
+            matches!(
+                kind(verify_moves(&start, &[], AutomaticMoves::Emulated)),
+                VerifyErrorKind::InvalidStart(_)
+            ),
+            eq(true)
+        );
+    }
+}